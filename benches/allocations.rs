@@ -0,0 +1,64 @@
+//! The allocations of this bench binary are counted to compare the sorts by their
+//! number of allocations, printed to stderr. It has its own target so that the
+//! counting allocator doesn't slow down the other benchmarks.
+//!
+//! ```text
+//! cargo +nightly bench --features nightly --bench allocations -- --nocapture
+//! ```
+
+#![feature(test)]
+
+extern crate test;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+use data_oriented_sort::DataOriented;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn bench_allocations<F: FnMut(&mut DataOriented)>(b: &mut test::Bencher, name: &str, mut sort: F) {
+    let data = DataOriented::from_seed(42, 1_000_000);
+    let mut sorted = data.clone();
+
+    let mut iterations = 0;
+    let before = ALLOCATIONS.load(Relaxed);
+    b.iter(|| {
+        data.clone_into(&mut sorted);
+        sort(&mut sorted);
+        iterations += 1;
+    });
+
+    eprintln!("{}: {} allocations per sort", name, (ALLOCATIONS.load(Relaxed) - before) / iterations);
+}
+
+// Reusing the permutations as the scratch leaves a single allocation per sort
+// against 6, the times are within the noise of each other, gathering into the
+// high halves then copying every column back costs about what the saved
+// allocations do.
+
+#[bench]
+fn allocations_five_scatters_1_000_000(b: &mut test::Bencher) {
+    bench_allocations(b, "five_scatters", DataOriented::sort_unstable)
+}
+
+#[bench]
+fn allocations_scratch_reuse_1_000_000(b: &mut test::Bencher) {
+    bench_allocations(b, "scratch_reuse", DataOriented::sort_unstable_scratch_reuse)
+}
@@ -0,0 +1,11 @@
+#![no_main]
+
+use data_oriented_sort::DataOriented;
+use libfuzzer_sys::fuzz_target;
+
+// `cargo +nightly fuzz run from_csv fuzz/corpus/from_csv`
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(data) = DataOriented::from_csv(bytes) {
+        data.check_invariants().unwrap();
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use data_oriented_sort::DataOriented;
+use libfuzzer_sys::fuzz_target;
+
+// `cargo +nightly fuzz run read_columns fuzz/corpus/read_columns`
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(data) = DataOriented::read_columns(bytes) {
+        data.check_invariants().unwrap();
+    }
+});
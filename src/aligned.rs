@@ -0,0 +1,340 @@
+//! Columns allocated on `SIMD_ALIGNMENT` bytes boundaries, so that the AVX2
+//! paths can use aligned loads. A `Vec` can't be over-aligned, its allocation
+//! is freed with the alignment of its element type, the columns are therefore
+//! stored in `AlignedBuf`s, fixed-length buffers owning their allocation.
+//! `AlignedDataOriented::sort_unstable_gather` writes the AVX2 gathers of the
+//! `simd` module with aligned stores.
+
+use std::alloc::{self, Layout};
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
+use std::{fmt, mem};
+
+use crate::simd::{gather_aligned_u16, gather_aligned_u32};
+use crate::{argsort_columns, DataOriented};
+
+/// The alignment of the `AlignedBuf`s, the size of an AVX2 register.
+pub const SIMD_ALIGNMENT: usize = 32;
+
+/// A fixed-length buffer of `Copy` values aligned on `SIMD_ALIGNMENT` bytes.
+/// The allocations are padded with zeroes to a multiple of `SIMD_ALIGNMENT`
+/// bytes, with room for a `u32` after the values, for the 4 bytes gathers.
+pub struct AlignedBuf<T: Copy> {
+    ptr: NonNull<T>,
+    len: usize,
+}
+
+// SAFETY: the buffer owns its values like a `Box<[T]>` would.
+unsafe impl<T: Copy + Send> Send for AlignedBuf<T> {}
+unsafe impl<T: Copy + Sync> Sync for AlignedBuf<T> {}
+
+impl<T: Copy> AlignedBuf<T> {
+    fn layout(len: usize) -> Layout {
+        let align = SIMD_ALIGNMENT.max(mem::align_of::<T>());
+        let size = mem::size_of::<T>().checked_mul(len).expect("capacity overflow");
+        let padded = match size {
+            0 => 0,
+            size => size
+                .checked_add(mem::size_of::<u32>() + SIMD_ALIGNMENT - 1)
+                .map(|size| size / SIMD_ALIGNMENT * SIMD_ALIGNMENT)
+                .expect("capacity overflow"),
+        };
+        Layout::from_size_align(padded, align).expect("capacity overflow")
+    }
+
+    /// Allocates `len` copies of `value`.
+    pub fn filled(len: usize, value: T) -> AlignedBuf<T> {
+        AlignedBuf::from_fn(len, |_| value)
+    }
+
+    pub fn from_slice(values: &[T]) -> AlignedBuf<T> {
+        AlignedBuf::from_fn(values.len(), |i| values[i])
+    }
+
+    /// Allocates room for `len` values and zeroes the padding after them.
+    fn allocate(len: usize) -> NonNull<T> {
+        let layout = AlignedBuf::<T>::layout(len);
+        let ptr = if layout.size() == 0 {
+            // a dangling but aligned pointer, like `NonNull::dangling` but over-aligned,
+            // without provenance as it is never dereferenced
+            ptr::without_provenance_mut(layout.align())
+        } else {
+            // SAFETY: the layout has a non-zero size.
+            unsafe { alloc::alloc(layout) as *mut T }
+        };
+
+        let ptr = match NonNull::new(ptr) {
+            Some(ptr) => ptr,
+            None => alloc::handle_alloc_error(layout),
+        };
+
+        let size = mem::size_of::<T>() * len;
+        // SAFETY: the padding lies between the values and the end of the allocation.
+        unsafe { ptr::write_bytes((ptr.as_ptr() as *mut u8).add(size), 0, layout.size() - size) };
+
+        ptr
+    }
+
+    pub(crate) fn from_fn<F: FnMut(usize) -> T>(len: usize, mut f: F) -> AlignedBuf<T> {
+        let ptr = AlignedBuf::<T>::allocate(len);
+        for i in 0..len {
+            // SAFETY: the allocation has room for `len` values, `T: Copy` can't
+            // panic on drop and `f` panicking only leaks the allocation.
+            unsafe { ptr::write(ptr.as_ptr().add(i), f(i)) };
+        }
+
+        AlignedBuf { ptr, len }
+    }
+
+    /// Allocates `len` values written by `init` through the pointer to the first one.
+    ///
+    /// # Safety
+    ///
+    /// `init` must write the `len` values, `init` panicking only leaks the allocation.
+    pub(crate) unsafe fn from_init<F: FnOnce(*mut T)>(len: usize, init: F) -> AlignedBuf<T> {
+        let ptr = AlignedBuf::<T>::allocate(len);
+        init(ptr.as_ptr());
+        AlignedBuf { ptr, len }
+    }
+
+    /// The pointer to the values which, unlike the one of the slice, can read the padding.
+    pub(crate) fn as_padded_ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+}
+
+impl<T: Copy> Deref for AlignedBuf<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: the `len` values are initialized and the pointer is aligned.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: Copy> DerefMut for AlignedBuf<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: the `len` values are initialized and the pointer is aligned.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: Copy> Drop for AlignedBuf<T> {
+    fn drop(&mut self) {
+        let layout = AlignedBuf::<T>::layout(self.len);
+        if layout.size() != 0 {
+            // SAFETY: the pointer was allocated with this same layout.
+            unsafe { alloc::dealloc(self.ptr.as_ptr() as *mut u8, layout) }
+        }
+    }
+}
+
+impl<T: Copy> Clone for AlignedBuf<T> {
+    fn clone(&self) -> AlignedBuf<T> {
+        AlignedBuf::from_slice(self)
+    }
+}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for AlignedBuf<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// The five columns of a `DataOriented` stored in `AlignedBuf`s.
+#[derive(Debug, Clone)]
+pub struct AlignedDataOriented {
+    query_index: AlignedBuf<u32>,
+    distance: AlignedBuf<u8>,
+    attribute: AlignedBuf<u16>,
+    word_index: AlignedBuf<u16>,
+    is_exact: AlignedBuf<bool>,
+}
+
+impl DataOriented {
+    /// Allocates `len` zeroed rows whose columns are aligned on `SIMD_ALIGNMENT`
+    /// bytes, the columns are then filled through the `*_mut` accessors.
+    pub fn aligned_zeroed(len: usize) -> AlignedDataOriented {
+        AlignedDataOriented {
+            query_index: AlignedBuf::filled(len, 0),
+            distance: AlignedBuf::filled(len, 0),
+            attribute: AlignedBuf::filled(len, 0),
+            word_index: AlignedBuf::filled(len, 0),
+            is_exact: AlignedBuf::filled(len, false),
+        }
+    }
+}
+
+impl AlignedDataOriented {
+    pub fn len(&self) -> usize {
+        self.query_index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.query_index.is_empty()
+    }
+
+    pub fn query_index(&self) -> &[u32] {
+        &self.query_index
+    }
+
+    pub fn distance(&self) -> &[u8] {
+        &self.distance
+    }
+
+    pub fn attribute(&self) -> &[u16] {
+        &self.attribute
+    }
+
+    pub fn word_index(&self) -> &[u16] {
+        &self.word_index
+    }
+
+    pub fn is_exact(&self) -> &[bool] {
+        &self.is_exact
+    }
+
+    pub fn query_index_mut(&mut self) -> &mut [u32] {
+        &mut self.query_index
+    }
+
+    pub fn distance_mut(&mut self) -> &mut [u8] {
+        &mut self.distance
+    }
+
+    pub fn attribute_mut(&mut self) -> &mut [u16] {
+        &mut self.attribute
+    }
+
+    pub fn word_index_mut(&mut self) -> &mut [u16] {
+        &mut self.word_index
+    }
+
+    pub fn is_exact_mut(&mut self) -> &mut [bool] {
+        &mut self.is_exact
+    }
+
+    /// Sorts the rows like `DataOriented::sort_unstable`, the columns stay aligned.
+    pub fn sort_unstable(&mut self) {
+        fn gather<T: Copy>(permutations: &[usize], column: &AlignedBuf<T>) -> AlignedBuf<T> {
+            AlignedBuf::from_fn(permutations.len(), |k| column[permutations[k]])
+        }
+
+        let mut permutations = Vec::new();
+        argsort_columns(
+            &self.query_index,
+            &self.distance,
+            &self.attribute,
+            &self.word_index,
+            &self.is_exact,
+            &mut permutations,
+        );
+
+        self.query_index = gather(&permutations, &self.query_index);
+        self.distance = gather(&permutations, &self.distance);
+        self.attribute = gather(&permutations, &self.attribute);
+        self.word_index = gather(&permutations, &self.word_index);
+        self.is_exact = gather(&permutations, &self.is_exact);
+    }
+
+    /// Sorts the rows like `sort_unstable` but gathers the `u32` and `u16` columns
+    /// with AVX2 when the CPU supports it, storing the values with aligned stores.
+    pub fn sort_unstable_gather(&mut self) {
+        fn gather<T: Copy>(permutations: &[usize], column: &AlignedBuf<T>) -> AlignedBuf<T> {
+            AlignedBuf::from_fn(permutations.len(), |k| column[permutations[k]])
+        }
+
+        let mut permutations = Vec::new();
+        argsort_columns(
+            &self.query_index,
+            &self.distance,
+            &self.attribute,
+            &self.word_index,
+            &self.is_exact,
+            &mut permutations,
+        );
+
+        self.query_index = gather_aligned_u32(&permutations, &self.query_index);
+        self.distance = gather(&permutations, &self.distance);
+        self.attribute = gather_aligned_u16(&permutations, &self.attribute);
+        self.word_index = gather_aligned_u16(&permutations, &self.word_index);
+        self.is_exact = gather(&permutations, &self.is_exact);
+    }
+
+    pub fn to_data_oriented(&self) -> DataOriented {
+        DataOriented {
+            query_index: self.query_index.to_vec(),
+            distance: self.distance.to_vec(),
+            attribute: self.attribute.to_vec(),
+            word_index: self.word_index.to_vec(),
+            is_exact: self.is_exact.to_vec(),
+        }
+    }
+}
+
+impl From<&DataOriented> for AlignedDataOriented {
+    fn from(data: &DataOriented) -> AlignedDataOriented {
+        AlignedDataOriented {
+            query_index: AlignedBuf::from_slice(&data.query_index),
+            distance: AlignedBuf::from_slice(&data.distance),
+            attribute: AlignedBuf::from_slice(&data.attribute),
+            word_index: AlignedBuf::from_slice(&data.word_index),
+            is_exact: AlignedBuf::from_slice(&data.is_exact),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_aligned<T>(column: &[T]) -> bool {
+        (column.as_ptr() as usize).is_multiple_of(SIMD_ALIGNMENT)
+    }
+
+    #[test]
+    fn columns_are_aligned() {
+        for &len in &[0, 1, 7, 1_000] {
+            let mut aligned = DataOriented::aligned_zeroed(len);
+            assert_eq!(aligned.len(), len);
+            assert!(is_aligned(aligned.query_index()));
+            assert!(is_aligned(aligned.distance()));
+            assert!(is_aligned(aligned.attribute()));
+            assert!(is_aligned(aligned.word_index()));
+            assert!(is_aligned(aligned.is_exact()));
+
+            let data = DataOriented::from_seed(42, len);
+            aligned.query_index_mut().copy_from_slice(data.query_index());
+            aligned.distance_mut().copy_from_slice(data.distance());
+            aligned.attribute_mut().copy_from_slice(data.attribute());
+            aligned.word_index_mut().copy_from_slice(data.word_index());
+            aligned.is_exact_mut().copy_from_slice(data.is_exact());
+            assert_eq!(aligned.to_data_oriented(), data);
+        }
+    }
+
+    #[test]
+    fn aligned_sort_is_valid() {
+        let data = DataOriented::from_seed(42, 1_000);
+
+        let mut aligned = AlignedDataOriented::from(&data);
+        aligned.sort_unstable();
+        assert!(is_aligned(aligned.query_index()));
+        assert!(is_aligned(aligned.word_index()));
+        assert_eq!(aligned.to_data_oriented(), data.to_sorted());
+    }
+
+    #[test]
+    fn aligned_gather_sort_is_valid() {
+        // both sides of the chunks of 8 and 16 values of the gathers
+        for &len in &[0, 1, 15, 16, 17, 1_000, 16_000] {
+            let data = DataOriented::from_seed(42, len);
+
+            let mut aligned = AlignedDataOriented::from(&data);
+            aligned.sort_unstable_gather();
+            assert!(is_aligned(aligned.query_index()));
+            assert!(is_aligned(aligned.attribute()));
+            assert_eq!(aligned.to_data_oriented(), data.to_sorted());
+        }
+    }
+}
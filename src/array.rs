@@ -0,0 +1,39 @@
+use ndarray::ArrayView1;
+
+use crate::DataOriented;
+
+macro_rules! column_view {
+    ($name:ident, $field:ident, $ty:ty) => {
+        /// Views the column as an `ndarray` array, without copying it.
+        pub fn $name(&self) -> ArrayView1<'_, $ty> {
+            ArrayView1::from(&self.$field[..])
+        }
+    };
+}
+
+impl DataOriented {
+    column_view!(query_index_view, query_index, u32);
+    column_view!(distance_view, distance, u8);
+    column_view!(attribute_view, attribute, u16);
+    column_view!(word_index_view, word_index, u16);
+    column_view!(is_exact_view, is_exact, bool);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn views_share_the_columns() {
+        let data = DataOriented::from_seed(42, 1_000);
+
+        let view = data.query_index_view();
+        assert_eq!(view.as_ptr(), data.query_index().as_ptr());
+
+        let expected: u64 = data.query_index().iter().map(|&q| q as u64).sum();
+        assert_eq!(view.mapv(u64::from).sum(), expected);
+
+        let exacts = data.is_exact_view().iter().filter(|&&e| e).count();
+        assert_eq!(exacts, data.is_exact().iter().filter(|&&e| e).count());
+    }
+}
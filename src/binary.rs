@@ -0,0 +1,486 @@
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+use crate::{is_permutation, Classic, ClassicRef, DataOriented, Error};
+
+/// The first bytes of the `write_columns` format.
+pub const COLUMNS_MAGIC: [u8; 4] = *b"DOSC";
+/// The version of the `write_columns` format, bumped on every incompatible change.
+pub const COLUMNS_VERSION: u16 = 1;
+/// The flag of a `query_index` column stored as the varint deltas of its sorted values.
+pub const DELTA_QUERY_INDEX: u16 = 1 << 0;
+const KNOWN_FLAGS: u16 = DELTA_QUERY_INDEX;
+
+/// The number of bytes of a row written by `to_packed_aos`, which is also
+/// the number of bytes of a row in the columns of `write_columns`.
+pub const PACKED_ROW_LEN: usize = 4 + 1 + 2 + 2 + 1;
+
+/// The number of bytes of the header of `write_columns`.
+pub(crate) const HEADER_LEN: usize = 16;
+
+/// The header of a sorted run written by `sort_and_write_run`,
+/// the bounds of the primary key are zero for an empty run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunMeta {
+    pub rows: u64,
+    pub min_query_index: u32,
+    pub max_query_index: u32,
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Returns the number of rows and the flags of a `write_columns` header after checking it.
+pub(crate) fn parse_header(header: &[u8; HEADER_LEN]) -> Result<(usize, u16), Error> {
+    if header[..4] != COLUMNS_MAGIC {
+        return Err(invalid_data("not a columns file, the magic number is wrong").into());
+    }
+
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version != COLUMNS_VERSION {
+        return Err(Error::VersionMismatch { expected: COLUMNS_VERSION, found: version });
+    }
+
+    let flags = u16::from_le_bytes([header[6], header[7]]);
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(invalid_data("unknown columns format flags").into());
+    }
+
+    let mut rows = [0; 8];
+    rows.copy_from_slice(&header[8..]);
+    let len = usize::try_from(u64::from_le_bytes(rows))
+        .map_err(|_| invalid_data("too many rows for this platform"))?;
+
+    Ok((len, flags))
+}
+
+pub(crate) fn header(len: usize, flags: u16) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..4].copy_from_slice(&COLUMNS_MAGIC);
+    header[4..6].copy_from_slice(&COLUMNS_VERSION.to_le_bytes());
+    header[6..8].copy_from_slice(&flags.to_le_bytes());
+    header[8..].copy_from_slice(&(len as u64).to_le_bytes());
+    header
+}
+
+pub(crate) fn check_u32_indices(len: usize) -> io::Result<()> {
+    match u32::try_from(len) {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            let message = "too many rows to be written as u32 indices";
+            Err(io::Error::new(io::ErrorKind::InvalidInput, message))
+        }
+    }
+}
+
+/// Writes the indices in the `write_sorted_indices` format, they must fit in `u32`s.
+pub(crate) fn write_u32_indices<W: Write>(indices: &[usize], mut writer: W) -> io::Result<()> {
+    for &i in indices {
+        writer.write_all(&(i as u32).to_le_bytes())?;
+    }
+
+    writer.flush()
+}
+
+/// Writes the differences between the consecutive values, the first one with
+/// zero, as LEB128 varints, seven bits per byte with the high bit set when more follow.
+fn write_deltas<W: Write>(writer: &mut W, sorted: &[u32]) -> io::Result<()> {
+    let mut previous = 0;
+    for &value in sorted {
+        let mut delta = value - previous;
+        previous = value;
+        while delta >= 0x80 {
+            writer.write_all(&[delta as u8 | 0x80])?;
+            delta >>= 7;
+        }
+        writer.write_all(&[delta as u8])?;
+    }
+    Ok(())
+}
+
+fn read_deltas<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<u32>> {
+    let mut column = Vec::new();
+    let mut previous: u32 = 0;
+    for _ in 0..len {
+        let mut delta: u32 = 0;
+        for shift in (0..).step_by(7) {
+            let mut byte = [0];
+            reader.read_exact(&mut byte)?;
+            let bits = (byte[0] & 0x7F) as u32;
+            if shift > 28 || (shift == 28 && bits > 0xF) {
+                return Err(invalid_data("a query_index delta overflows a u32"));
+            }
+            delta |= bits << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        previous = previous.checked_add(delta).ok_or_else(|| invalid_data("a query_index overflows a u32"))?;
+        column.push(previous);
+    }
+    Ok(column)
+}
+
+/// Encodes a row like `DataOriented::to_packed_aos`.
+pub(crate) fn encode_packed_row(row: ClassicRef<'_>) -> [u8; PACKED_ROW_LEN] {
+    let mut bytes = [0; PACKED_ROW_LEN];
+    bytes[..4].copy_from_slice(&row.query_index.to_le_bytes());
+    bytes[4] = *row.distance;
+    bytes[5..7].copy_from_slice(&row.attribute.to_le_bytes());
+    bytes[7..9].copy_from_slice(&row.word_index.to_le_bytes());
+    bytes[9] = *row.is_exact as u8;
+    bytes
+}
+
+/// Decodes a row written by `DataOriented::to_packed_aos`.
+pub(crate) fn decode_packed_row(bytes: &[u8]) -> io::Result<Classic> {
+    let is_exact = match bytes[9] {
+        0 => false,
+        1 => true,
+        _ => return Err(invalid_data("is_exact must be 0 or 1")),
+    };
+
+    Ok(Classic {
+        query_index: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        distance: bytes[4],
+        attribute: u16::from_le_bytes([bytes[5], bytes[6]]),
+        word_index: u16::from_le_bytes([bytes[7], bytes[8]]),
+        is_exact,
+    })
+}
+
+fn read_column<R, T, F, const N: usize>(
+    reader: &mut R,
+    len: usize,
+    from_bytes: F,
+) -> io::Result<Vec<T>>
+where
+    R: Read,
+    F: Fn([u8; N]) -> T,
+{
+    let mut bytes = [0; N];
+    let mut column = Vec::new();
+    for _ in 0..len {
+        reader.read_exact(&mut bytes)?;
+        column.push(from_bytes(bytes));
+    }
+    Ok(column)
+}
+
+impl DataOriented {
+    /// Writes a 16 bytes header, the `COLUMNS_MAGIC`, the `COLUMNS_VERSION` and
+    /// the flags, zero here, as little-endian `u16`s and the row count as a little-endian
+    /// `u64`, followed by every column in little-endian, ordered by decreasing
+    /// alignment: `query_index`, `attribute`, `word_index`, `distance` and `is_exact`
+    /// as a byte.
+    pub fn write_columns<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_columns_with_flags(writer, 0)
+    }
+
+    /// Writes the columns like `write_columns` but, when the rows are ordered by
+    /// `query_index`, like after a sort, this column is stored as varint deltas
+    /// and the `DELTA_QUERY_INDEX` flag is set. The other columns are unchanged.
+    pub fn write_columns_compressed<W: Write>(&self, writer: W) -> io::Result<()> {
+        let sorted = self.query_index.windows(2).all(|w| w[0] <= w[1]);
+        let flags = if sorted { DELTA_QUERY_INDEX } else { 0 };
+        self.write_columns_with_flags(writer, flags)
+    }
+
+    fn write_columns_with_flags<W: Write>(&self, mut writer: W, flags: u16) -> io::Result<()> {
+        writer.write_all(&header(self.len(), flags))?;
+        if flags & DELTA_QUERY_INDEX != 0 {
+            write_deltas(&mut writer, &self.query_index)?;
+        } else {
+            for q in &self.query_index {
+                writer.write_all(&q.to_le_bytes())?;
+            }
+        }
+        for a in &self.attribute {
+            writer.write_all(&a.to_le_bytes())?;
+        }
+        for w in &self.word_index {
+            writer.write_all(&w.to_le_bytes())?;
+        }
+        writer.write_all(&self.distance)?;
+        for &e in &self.is_exact {
+            writer.write_all(&[e as u8])?;
+        }
+        writer.flush()
+    }
+
+    /// Reads the columns written by `write_columns` or `write_columns_compressed`,
+    /// an input written with another format version is rejected with `VersionMismatch`.
+    pub fn read_columns<R: Read>(mut reader: R) -> Result<DataOriented, Error> {
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        let (len, flags) = parse_header(&header)?;
+
+        let query_index = if flags & DELTA_QUERY_INDEX != 0 {
+            read_deltas(&mut reader, len)?
+        } else {
+            read_column(&mut reader, len, u32::from_le_bytes)?
+        };
+        let attribute = read_column(&mut reader, len, u16::from_le_bytes)?;
+        let word_index = read_column(&mut reader, len, u16::from_le_bytes)?;
+        let distance = read_column(&mut reader, len, |[d]: [u8; 1]| d)?;
+        let is_exact = read_column(&mut reader, len, |[e]: [u8; 1]| e)?
+            .into_iter()
+            .map(|e| match e {
+                0 => Ok(false),
+                1 => Ok(true),
+                _ => Err(invalid_data("is_exact must be 0 or 1")),
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(DataOriented { query_index, distance, attribute, word_index, is_exact })
+    }
+
+    /// Interleaves the columns into rows of `PACKED_ROW_LEN` bytes without padding,
+    /// in the fields order: `query_index`, `distance`, `attribute`, `word_index`
+    /// in little-endian and `is_exact` as a byte.
+    pub fn to_packed_aos(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.len() * PACKED_ROW_LEN);
+        for row in self.iter() {
+            bytes.extend_from_slice(&encode_packed_row(row));
+        }
+        bytes
+    }
+
+    /// Splits rows written by `to_packed_aos` back into columns.
+    pub fn from_packed_aos(bytes: &[u8]) -> Result<DataOriented, Error> {
+        if !bytes.len().is_multiple_of(PACKED_ROW_LEN) {
+            return Err(invalid_data("the bytes are not a whole number of rows").into());
+        }
+
+        let mut data = DataOriented::with_capacity(bytes.len() / PACKED_ROW_LEN);
+        for row in bytes.chunks_exact(PACKED_ROW_LEN) {
+            data.push(decode_packed_row(row)?);
+        }
+
+        Ok(data)
+    }
+
+    /// Sorts the rows and writes them as a run for an external merge: the
+    /// `RunMeta` bounds as two little-endian `u32` then the `write_columns` format.
+    pub fn sort_and_write_run<W: Write>(&mut self, mut writer: W) -> io::Result<RunMeta> {
+        self.sort_unstable();
+
+        let meta = RunMeta {
+            rows: self.len() as u64,
+            min_query_index: self.query_index.first().copied().unwrap_or(0),
+            max_query_index: self.query_index.last().copied().unwrap_or(0),
+        };
+
+        writer.write_all(&meta.min_query_index.to_le_bytes())?;
+        writer.write_all(&meta.max_query_index.to_le_bytes())?;
+        self.write_columns(writer)?;
+
+        Ok(meta)
+    }
+
+    /// Reads a run written by `sort_and_write_run`.
+    pub fn read_run<R: Read>(mut reader: R) -> Result<(RunMeta, DataOriented), Error> {
+        let mut bounds = [0; 8];
+        reader.read_exact(&mut bounds)?;
+        let data = DataOriented::read_columns(reader)?;
+
+        let meta = RunMeta {
+            rows: data.len() as u64,
+            min_query_index: u32::from_le_bytes([bounds[0], bounds[1], bounds[2], bounds[3]]),
+            max_query_index: u32::from_le_bytes([bounds[4], bounds[5], bounds[6], bounds[7]]),
+        };
+
+        Ok((meta, data))
+    }
+
+    /// Writes the sorted order of the rows as little-endian `u32` indices,
+    /// the columns are never cloned nor moved. The writer should be buffered.
+    pub fn write_sorted_indices<W: Write>(&self, writer: W) -> io::Result<()> {
+        check_u32_indices(self.len())?;
+        write_u32_indices(&self.argsort_unstable(), writer)
+    }
+
+    /// Writes the sorted order of the rows, without the rows, as the number
+    /// of indices in a little-endian `u64` then the `write_sorted_indices` output.
+    pub fn save_order<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+        self.write_sorted_indices(writer)
+    }
+
+    /// Reads an order written by `save_order` and moves the rows accordingly,
+    /// the order must have been saved from rows of the same length.
+    pub fn load_and_apply<R: Read>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut len = [0; 8];
+        reader.read_exact(&mut len)?;
+        if u64::from_le_bytes(len) != self.len() as u64 {
+            return Err(invalid_data("the order doesn't have one index per row"));
+        }
+
+        let permutations = read_column(&mut reader, self.len(), |b| u32::from_le_bytes(b) as usize)?;
+        if !is_permutation(&permutations) {
+            return Err(invalid_data("the order is not a permutation of the rows"));
+        }
+
+        self.apply_permutation(&permutations);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::merge_sorted;
+
+    #[test]
+    fn columns_round_trip() {
+        let data = DataOriented::from_seed(42, 1_000);
+
+        let mut bytes = Vec::new();
+        data.write_columns(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 16 + data.len() * 10);
+        assert_eq!(bytes[..4], COLUMNS_MAGIC);
+
+        let read = DataOriented::read_columns(&bytes[..]).unwrap();
+        assert_eq!(read, data);
+
+        match DataOriented::read_columns(&bytes[..bytes.len() - 1]) {
+            Err(Error::Io(error)) => assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+
+        bytes[0] = b'X';
+        match DataOriented::read_columns(&bytes[..]) {
+            Err(Error::Io(error)) => assert_eq!(error.kind(), io::ErrorKind::InvalidData),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn compressed_columns_round_trip() {
+        let mut data = DataOriented::from_seed(42, 10_000);
+
+        // not sorted by query_index, written like `write_columns`
+        let mut raw = Vec::new();
+        data.write_columns(&mut raw).unwrap();
+        let mut compressed = Vec::new();
+        data.write_columns_compressed(&mut compressed).unwrap();
+        assert_eq!(compressed, raw);
+
+        data.sort_unstable();
+        raw.clear();
+        data.write_columns(&mut raw).unwrap();
+        compressed.clear();
+        data.write_columns_compressed(&mut compressed).unwrap();
+        assert_eq!(u16::from_le_bytes([compressed[6], compressed[7]]), DELTA_QUERY_INDEX);
+        assert_eq!(DataOriented::read_columns(&compressed[..]).unwrap(), data);
+
+        println!(
+            "{} bytes compressed against {} raw, {:.1}% smaller",
+            compressed.len(),
+            raw.len(),
+            100.0 - compressed.len() as f64 * 100.0 / raw.len() as f64,
+        );
+        assert!(compressed.len() < raw.len());
+
+        // a delta longer than a u32
+        compressed[16..21].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0x7F]);
+        match DataOriented::read_columns(&compressed[..]) {
+            Err(Error::Io(error)) => assert_eq!(error.kind(), io::ErrorKind::InvalidData),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn packed_aos_round_trip() {
+        let data = DataOriented::from_seed(42, 1_000);
+
+        let bytes = data.to_packed_aos();
+        assert_eq!(bytes.len(), data.len() * PACKED_ROW_LEN);
+        assert_eq!(DataOriented::from_packed_aos(&bytes).unwrap(), data);
+
+        // as compact as the exactly sized columns, the structures are padded
+        let columns = DataOriented::from_columns(
+            data.query_index().to_vec(),
+            data.distance().to_vec(),
+            data.attribute().to_vec(),
+            data.word_index().to_vec(),
+            data.is_exact().to_vec(),
+        )
+        .unwrap();
+        assert_eq!(bytes.len(), columns.memory_footprint());
+        assert!(bytes.len() < data.len() * std::mem::size_of::<Classic>());
+
+        assert!(DataOriented::from_packed_aos(&bytes[1..]).is_err());
+        let mut bytes = bytes;
+        bytes[9] = 2;
+        assert!(DataOriented::from_packed_aos(&bytes).is_err());
+    }
+
+    #[test]
+    fn runs_merge_back() {
+        let mut first = DataOriented::from_seed(1, 500);
+        let mut second = DataOriented::from_seed(2, 700);
+        let mut expected = first.clone();
+        expected.append(&mut second.clone());
+        expected.sort_unstable();
+
+        let mut first_run = Vec::new();
+        let first_meta = first.sort_and_write_run(&mut first_run).unwrap();
+        let mut second_run = Vec::new();
+        let second_meta = second.sort_and_write_run(&mut second_run).unwrap();
+        assert_eq!(first_meta.rows, 500);
+        assert_eq!(first_meta.min_query_index, *first.query_index().iter().min().unwrap());
+        assert_eq!(second_meta.max_query_index, *second.query_index().iter().max().unwrap());
+
+        let (meta, first) = DataOriented::read_run(&first_run[..]).unwrap();
+        assert_eq!(meta, first_meta);
+        let (meta, second) = DataOriented::read_run(&second_run[..]).unwrap();
+        assert_eq!(meta, second_meta);
+
+        assert_eq!(merge_sorted(&first, &second), expected);
+    }
+
+    #[test]
+    fn saved_order_sorts_the_raw_rows() {
+        let mut order = Vec::new();
+        DataOriented::from_seed(42, 1_000).save_order(&mut order).unwrap();
+        assert_eq!(order.len(), 8 + 1_000 * 4);
+
+        let mut data = DataOriented::from_seed(42, 1_000);
+        data.load_and_apply(&order[..]).unwrap();
+        assert_eq!(data, DataOriented::from_seed(42, 1_000).to_sorted());
+
+        let mut shorter = DataOriented::from_seed(42, 999);
+        let error = shorter.load_and_apply(&order[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(shorter, DataOriented::from_seed(42, 999));
+
+        // a duplicated index
+        order.copy_within(12..16, 8);
+        let error = data.load_and_apply(&order[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn sorted_indices_reproduce_the_sort() {
+        let data = DataOriented::from_seed(42, 1_000);
+
+        let mut bytes = Vec::new();
+        data.write_sorted_indices(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), data.len() * 4);
+
+        let indices: Vec<usize> = bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as usize)
+            .collect();
+
+        let mut sorted = data.clone();
+        sorted.sort_unstable();
+
+        for (row, &i) in sorted.iter().zip(&indices) {
+            assert_eq!(row, data.get(i).unwrap());
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{DataOriented, Error};
+
+/// Builds a `DataOriented` one field at a time, for when the columns
+/// are produced separately, the lengths are only checked by `build`.
+#[derive(Debug, Default, Clone)]
+pub struct DataOrientedBuilder {
+    query_index: Vec<u32>,
+    distance: Vec<u8>,
+    attribute: Vec<u16>,
+    word_index: Vec<u16>,
+    is_exact: Vec<bool>,
+}
+
+impl DataOrientedBuilder {
+    pub fn new() -> DataOrientedBuilder {
+        DataOrientedBuilder::default()
+    }
+
+    pub fn push_query_index(&mut self, query_index: u32) {
+        self.query_index.push(query_index);
+    }
+
+    pub fn push_distance(&mut self, distance: u8) {
+        self.distance.push(distance);
+    }
+
+    pub fn push_attribute(&mut self, attribute: u16) {
+        self.attribute.push(attribute);
+    }
+
+    pub fn push_word_index(&mut self, word_index: u16) {
+        self.word_index.push(word_index);
+    }
+
+    pub fn push_is_exact(&mut self, is_exact: bool) {
+        self.is_exact.push(is_exact);
+    }
+
+    /// Returns the built instance or the first column whose length
+    /// differs from the `query_index` one.
+    pub fn build(self) -> Result<DataOriented, Error> {
+        DataOriented::from_columns(self.query_index, self.distance, self.attribute, self.word_index, self.is_exact)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Column;
+
+    #[test]
+    fn build_aligned_columns() {
+        let mut builder = DataOrientedBuilder::new();
+        for i in 0..10 {
+            builder.push_query_index(i);
+        }
+        for i in 0..10 {
+            builder.push_distance(i as u8);
+            builder.push_attribute(i as u16);
+        }
+        for i in 0..10 {
+            builder.push_word_index(i as u16);
+            builder.push_is_exact(i % 3 == 1);
+        }
+
+        let data = builder.build().unwrap();
+        assert_eq!(data.len(), 10);
+        assert_eq!(data.get(7).unwrap().to_classic().word_index, 7);
+    }
+
+    #[test]
+    fn build_mismatched_columns() {
+        let mut builder = DataOrientedBuilder::new();
+        for i in 0..10 {
+            builder.push_query_index(i);
+            builder.push_distance(i as u8);
+            builder.push_attribute(i as u16);
+            builder.push_is_exact(false);
+        }
+        builder.push_word_index(0);
+
+        match builder.build() {
+            Err(Error::LengthMismatch { column: Column::WordIndex, expected: 10, found: 1 }) => (),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::hash_map::{DefaultHasher, HashMap};
+use std::hash::{Hash, Hasher};
+
+use crate::DataOriented;
+
+/// Remembers the sorting permutations of already seen datasets,
+/// indexed by a hash of their five columns.
+#[derive(Debug, Default, Clone)]
+pub struct PermCache {
+    entries: HashMap<u64, Vec<(DataOriented, Vec<usize>)>>,
+    hits: usize,
+}
+
+impl PermCache {
+    pub fn new() -> PermCache {
+        PermCache::default()
+    }
+
+    /// The number of sorts that reused a cached permutation.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl DataOriented {
+    /// Sorts the rows like `sort_unstable` but reuses the permutation
+    /// computed for an identical dataset if `cache` already knows it.
+    ///
+    /// Every entry keeps a copy of the unsorted columns to compare against,
+    /// a hash collision therefore never reuses the wrong permutation.
+    pub fn sort_cached(&mut self, cache: &mut PermCache) {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let entries = cache.entries.entry(hash).or_default();
+        match entries.iter().find(|(data, _)| data == self) {
+            Some((_, permutations)) => {
+                cache.hits += 1;
+                self.apply_permutation(permutations);
+            }
+            None => {
+                let permutations = self.argsort_unstable();
+                entries.push((self.clone(), permutations));
+                let (_, permutations) = entries.last().unwrap();
+                self.apply_permutation(permutations);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unchanged_data_reuses_the_permutation() {
+        let data = DataOriented::from_seed(42, 1_000);
+        let mut cache = PermCache::new();
+
+        let mut first = data.clone();
+        first.sort_cached(&mut cache);
+        assert_eq!(cache.hits(), 0);
+
+        let mut second = data.clone();
+        second.sort_cached(&mut cache);
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.len(), 1);
+
+        let mut expected = data;
+        expected.sort_unstable();
+        assert_eq!(first, expected);
+        assert_eq!(second, expected);
+    }
+
+    #[test]
+    fn different_data_misses() {
+        let mut cache = PermCache::new();
+
+        DataOriented::from_seed(1, 100).sort_cached(&mut cache);
+        DataOriented::from_seed(2, 100).sort_cached(&mut cache);
+        assert_eq!(cache.hits(), 0);
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,186 @@
+//! The five columns stored in a single allocation, the columns are laid out
+//! one after the other by decreasing alignment so that no padding is needed:
+//! `query_index`, `attribute`, `word_index`, `distance` then `is_exact`.
+
+use std::mem::size_of;
+use std::slice;
+
+use crate::{argsort_columns, ClassicRef, DataOriented};
+
+type Columns<'a> = (&'a [u32], &'a [u8], &'a [u16], &'a [u16], &'a [bool]);
+type ColumnsMut<'a> = (&'a mut [u32], &'a mut [u8], &'a mut [u16], &'a mut [u16], &'a mut [bool]);
+
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct CompactDataOriented {
+    // u32 words to respect the alignment of the first column
+    buffer: Vec<u32>,
+    len: usize,
+}
+
+impl CompactDataOriented {
+    fn zeroed(len: usize) -> CompactDataOriented {
+        let bytes = len * (size_of::<u32>() + 2 * size_of::<u16>() + size_of::<u8>() + size_of::<bool>());
+        let words = bytes.div_ceil(size_of::<u32>());
+        CompactDataOriented { buffer: vec![0; words], len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn query_index(&self) -> &[u32] {
+        self.columns().0
+    }
+
+    pub fn distance(&self) -> &[u8] {
+        self.columns().1
+    }
+
+    pub fn attribute(&self) -> &[u16] {
+        self.columns().2
+    }
+
+    pub fn word_index(&self) -> &[u16] {
+        self.columns().3
+    }
+
+    pub fn is_exact(&self) -> &[bool] {
+        self.columns().4
+    }
+
+    fn columns(&self) -> Columns<'_> {
+        let len = self.len;
+        let base = self.buffer.as_ptr() as *const u8;
+        // SAFETY: the buffer is large enough for the five columns, which do not overlap,
+        // each column offset respects its alignment as the previous ones are larger,
+        // the buffer is zero initialized and booleans are only written from `bool`s.
+        unsafe {
+            (
+                slice::from_raw_parts(base as *const u32, len),
+                slice::from_raw_parts(base.add(8 * len), len),
+                slice::from_raw_parts(base.add(4 * len) as *const u16, len),
+                slice::from_raw_parts(base.add(6 * len) as *const u16, len),
+                slice::from_raw_parts(base.add(9 * len) as *const bool, len),
+            )
+        }
+    }
+
+    fn columns_mut(&mut self) -> ColumnsMut<'_> {
+        let len = self.len;
+        let base = self.buffer.as_mut_ptr() as *mut u8;
+        // SAFETY: see `columns`, the mutable slices do not overlap.
+        unsafe {
+            (
+                slice::from_raw_parts_mut(base as *mut u32, len),
+                slice::from_raw_parts_mut(base.add(8 * len), len),
+                slice::from_raw_parts_mut(base.add(4 * len) as *mut u16, len),
+                slice::from_raw_parts_mut(base.add(6 * len) as *mut u16, len),
+                slice::from_raw_parts_mut(base.add(9 * len) as *mut bool, len),
+            )
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ClassicRef<'_>> + '_ {
+        let (query_index, distance, attribute, word_index, is_exact) = self.columns();
+        (0..self.len).map(move |i| ClassicRef {
+            query_index: &query_index[i],
+            distance: &distance[i],
+            attribute: &attribute[i],
+            word_index: &word_index[i],
+            is_exact: &is_exact[i],
+        })
+    }
+
+    pub fn argsort_unstable(&self) -> Vec<usize> {
+        let (query_index, distance, attribute, word_index, is_exact) = self.columns();
+        let mut permutations = Vec::new();
+        argsort_columns(query_index, distance, attribute, word_index, is_exact, &mut permutations);
+        permutations
+    }
+
+    /// Sorts the rows like `DataOriented::sort_unstable`.
+    pub fn sort_unstable(&mut self) {
+        let permutations = self.argsort_unstable();
+        let (query_index, distance, attribute, word_index, is_exact) = self.columns_mut();
+
+        apply_permutations_to_slice(&permutations, query_index, &mut Vec::new());
+        apply_permutations_to_slice(&permutations, distance, &mut Vec::new());
+        apply_permutations_to_slice(&permutations, attribute, &mut Vec::new());
+        apply_permutations_to_slice(&permutations, word_index, &mut Vec::new());
+        apply_permutations_to_slice(&permutations, is_exact, &mut Vec::new());
+    }
+
+    /// Returns the number of bytes allocated by the single buffer.
+    pub fn memory_footprint(&self) -> usize {
+        self.buffer.capacity() * size_of::<u32>()
+    }
+
+    pub fn to_data_oriented(&self) -> DataOriented {
+        let (query_index, distance, attribute, word_index, is_exact) = self.columns();
+        DataOriented {
+            query_index: query_index.to_vec(),
+            distance: distance.to_vec(),
+            attribute: attribute.to_vec(),
+            word_index: word_index.to_vec(),
+            is_exact: is_exact.to_vec(),
+        }
+    }
+}
+
+/// Applies the permutations to a slice, gathering into `scratch` and copying back.
+fn apply_permutations_to_slice<T: Copy>(permutations: &[usize], slice: &mut [T], scratch: &mut Vec<T>) {
+    assert_eq!(permutations.len(), slice.len());
+
+    scratch.clear();
+    scratch.extend(permutations.iter().map(|&i| slice[i]));
+    slice.copy_from_slice(scratch);
+}
+
+impl From<&DataOriented> for CompactDataOriented {
+    fn from(data: &DataOriented) -> CompactDataOriented {
+        let mut compact = CompactDataOriented::zeroed(data.len());
+        let (query_index, distance, attribute, word_index, is_exact) = compact.columns_mut();
+
+        query_index.copy_from_slice(data.query_index());
+        distance.copy_from_slice(data.distance());
+        attribute.copy_from_slice(data.attribute());
+        word_index.copy_from_slice(data.word_index());
+        is_exact.copy_from_slice(data.is_exact());
+
+        compact
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_sort_is_valid() {
+        for &len in &[0, 1, 3, 16_001] {
+            let mut data = DataOriented::from_seed(42, len);
+            let mut compact = CompactDataOriented::from(&data);
+            assert_eq!(compact.to_data_oriented(), data);
+
+            data.sort_unstable();
+            compact.sort_unstable();
+
+            assert_eq!(compact.to_data_oriented(), data);
+            assert!(compact.iter().eq(data.iter()));
+        }
+    }
+
+    #[test]
+    fn compact_memory_footprint() {
+        let data = DataOriented::from_seed(42, 16_001);
+        let compact = CompactDataOriented::from(&data);
+
+        // only the rounding to the buffer words can be added
+        assert_eq!(data.memory_footprint(), 10 * 16_001);
+        assert_eq!(compact.memory_footprint(), 10 * 16_001 + 2);
+    }
+}
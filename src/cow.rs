@@ -0,0 +1,157 @@
+use std::borrow::Cow;
+
+use crate::{apply_permutations, argsort_columns, check_column_lengths, DataOriented, Error};
+
+/// A `DataOriented` whose columns can be borrowed, the columns are only
+/// copied when the rows are moved, the argsort works directly on the borrows.
+#[derive(Debug, Clone)]
+pub struct DataOrientedCow<'a> {
+    query_index: Cow<'a, [u32]>,
+    distance: Cow<'a, [u8]>,
+    attribute: Cow<'a, [u16]>,
+    word_index: Cow<'a, [u16]>,
+    is_exact: Cow<'a, [bool]>,
+}
+
+/// Gathers a borrowed column directly into an owned one, `to_mut` would first
+/// copy it unpermuted, only the owned columns are permuted in place.
+fn apply_permutations_cow<T: Clone>(permutations: &[usize], column: &mut Cow<'_, [T]>) {
+    match column {
+        Cow::Borrowed(slice) => {
+            let slice: &[T] = slice;
+            *column = Cow::Owned(permutations.iter().map(|&i| slice[i].clone()).collect());
+        }
+        Cow::Owned(vec) => apply_permutations(permutations, vec),
+    }
+}
+
+impl DataOriented {
+    /// Like `from_columns` but accepts borrowed as well as owned columns.
+    pub fn from_columns_cow<'a>(
+        query_index: Cow<'a, [u32]>,
+        distance: Cow<'a, [u8]>,
+        attribute: Cow<'a, [u16]>,
+        word_index: Cow<'a, [u16]>,
+        is_exact: Cow<'a, [bool]>,
+    ) -> Result<DataOrientedCow<'a>, Error> {
+        check_column_lengths(&query_index, &distance, &attribute, &word_index, &is_exact)?;
+        Ok(DataOrientedCow { query_index, distance, attribute, word_index, is_exact })
+    }
+}
+
+impl DataOrientedCow<'_> {
+    pub fn len(&self) -> usize {
+        self.query_index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.query_index.is_empty()
+    }
+
+    /// Whether none of the columns has been copied.
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.query_index, Cow::Borrowed(_))
+            && matches!(self.distance, Cow::Borrowed(_))
+            && matches!(self.attribute, Cow::Borrowed(_))
+            && matches!(self.word_index, Cow::Borrowed(_))
+            && matches!(self.is_exact, Cow::Borrowed(_))
+    }
+
+    /// Returns the indices of the rows in sorted order, never copies the columns.
+    pub fn argsort_unstable(&self) -> Vec<usize> {
+        let mut permutations = Vec::new();
+        argsort_columns(
+            &self.query_index,
+            &self.distance,
+            &self.attribute,
+            &self.word_index,
+            &self.is_exact,
+            &mut permutations,
+        );
+        permutations
+    }
+
+    /// Sorts the rows, the borrowed columns are gathered into owned ones.
+    pub fn sort_unstable(&mut self) {
+        let permutations = self.argsort_unstable();
+        apply_permutations_cow(&permutations, &mut self.query_index);
+        apply_permutations_cow(&permutations, &mut self.distance);
+        apply_permutations_cow(&permutations, &mut self.attribute);
+        apply_permutations_cow(&permutations, &mut self.word_index);
+        apply_permutations_cow(&permutations, &mut self.is_exact);
+    }
+
+    pub fn into_owned(self) -> DataOriented {
+        DataOriented {
+            query_index: self.query_index.into_owned(),
+            distance: self.distance.into_owned(),
+            attribute: self.attribute.into_owned(),
+            word_index: self.word_index.into_owned(),
+            is_exact: self.is_exact.into_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn borrow(data: &DataOriented) -> DataOrientedCow<'_> {
+        DataOriented::from_columns_cow(
+            Cow::Borrowed(data.query_index()),
+            Cow::Borrowed(data.distance()),
+            Cow::Borrowed(data.attribute()),
+            Cow::Borrowed(data.word_index()),
+            Cow::Borrowed(data.is_exact()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn argsort_keeps_the_borrows() {
+        let data = DataOriented::from_seed(42, 1_000);
+        let cow = borrow(&data);
+
+        assert_eq!(cow.argsort_unstable(), data.argsort_unstable());
+        assert!(cow.is_borrowed());
+    }
+
+    #[test]
+    fn sort_copies_the_borrows() {
+        let data = DataOriented::from_seed(42, 1_000);
+        let mut cow = borrow(&data);
+        cow.sort_unstable();
+        assert!(!cow.is_borrowed());
+
+        assert_eq!(cow.into_owned(), data.to_sorted());
+    }
+
+    #[test]
+    fn sort_mixes_borrowed_and_owned_columns() {
+        let data = DataOriented::from_seed(42, 1_000);
+        let mut cow = DataOriented::from_columns_cow(
+            Cow::Owned(data.query_index().to_vec()),
+            Cow::Borrowed(data.distance()),
+            Cow::Owned(data.attribute().to_vec()),
+            Cow::Borrowed(data.word_index()),
+            Cow::Borrowed(data.is_exact()),
+        )
+        .unwrap();
+        cow.sort_unstable();
+
+        assert!(matches!(cow.distance, Cow::Owned(_)));
+        assert_eq!(cow.into_owned(), data.to_sorted());
+    }
+
+    #[test]
+    fn mismatching_lengths_are_rejected() {
+        let result = DataOriented::from_columns_cow(
+            Cow::Owned(vec![1, 2]),
+            Cow::Owned(vec![1, 2]),
+            Cow::Owned(vec![1]),
+            Cow::Owned(vec![1, 2]),
+            Cow::Owned(vec![true, false]),
+        );
+        assert!(matches!(result, Err(Error::LengthMismatch { expected: 2, found: 1, .. })));
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt::Write;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use crate::{Column, DataOriented, Error};
+
+impl DataOriented {
+    /// Reads rows from a comma-separated input with a header line
+    /// naming the five columns in declaration order.
+    pub fn from_csv<R: BufRead>(reader: R) -> Result<DataOriented, Error> {
+        let expected_header = Column::ALL.iter().map(|c| c.name()).collect::<Vec<_>>().join(",");
+
+        let mut lines = reader.lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        if header.trim() != expected_header {
+            return Err(Error::SchemaMismatch { expected: expected_header, found: header });
+        }
+
+        let mut data = DataOriented::default();
+        for (i, line) in lines.enumerate() {
+            let line_number = i + 2;
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let mut fields = line.split(',').map(str::trim);
+            let mut next = |column: Column| {
+                fields.next().ok_or_else(|| Error::Parse {
+                    line: line_number,
+                    message: format!("missing the {} field", column),
+                })
+            };
+
+            data.query_index.push(parse(line_number, Column::QueryIndex, next(Column::QueryIndex)?)?);
+            data.distance.push(parse(line_number, Column::Distance, next(Column::Distance)?)?);
+            data.attribute.push(parse(line_number, Column::Attribute, next(Column::Attribute)?)?);
+            data.word_index.push(parse(line_number, Column::WordIndex, next(Column::WordIndex)?)?);
+            data.is_exact.push(parse(line_number, Column::IsExact, next(Column::IsExact)?)?);
+
+            if fields.next().is_some() {
+                let message = format!("more than {} fields", Column::ALL.len());
+                return Err(Error::Parse { line: line_number, message });
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Returns one line per row with the comma-separated fields, in declaration order,
+    /// this format is stable and meant to be compared in tests.
+    pub fn to_snapshot(&self) -> String {
+        let mut snapshot = String::with_capacity(self.len() * 16);
+        for row in self.iter() {
+            let _ = writeln!(
+                snapshot,
+                "{},{},{},{},{}",
+                row.query_index, row.distance, row.attribute, row.word_index, row.is_exact,
+            );
+        }
+        snapshot
+    }
+}
+
+fn parse<T>(line: usize, column: Column, field: &str) -> Result<T, Error>
+where T: FromStr,
+      T::Err: std::fmt::Display,
+{
+    field.parse().map_err(|e| Error::Parse {
+        line,
+        message: format!("invalid {} {:?}: {}", column, field, e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_csv_reads_rows() {
+        let csv = "query_index,distance,attribute,word_index,is_exact\n\
+                   3,1,4,1,true\n\
+                   5,9,2,6,false\n";
+
+        let data = DataOriented::from_csv(csv.as_bytes()).unwrap();
+        let expected = DataOriented::from_columns(
+            vec![3, 5],
+            vec![1, 9],
+            vec![4, 2],
+            vec![1, 6],
+            vec![true, false],
+        ).unwrap();
+
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn snapshot_is_row_oriented() {
+        let data = DataOriented::from_columns(
+            vec![3, 5, 3],
+            vec![1, 9, 0],
+            vec![4, 2, 7],
+            vec![1, 6, 8],
+            vec![true, false, false],
+        ).unwrap();
+
+        assert_eq!(data.to_snapshot(), "3,1,4,1,true\n5,9,2,6,false\n3,0,7,8,false\n");
+        assert_eq!(data.to_sorted().to_snapshot(), "3,0,7,8,false\n3,1,4,1,true\n5,9,2,6,false\n");
+        assert_eq!(DataOriented::default().to_snapshot(), "");
+    }
+}
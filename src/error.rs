@@ -0,0 +1,115 @@
+use std::{error, fmt, io};
+
+use crate::Column;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A column doesn't have the same number of rows as the first one.
+    LengthMismatch { column: Column, expected: usize, found: usize },
+    /// The input doesn't describe the five expected columns.
+    SchemaMismatch { expected: String, found: String },
+    /// A value can't be parsed, `line` starts at one.
+    Parse { line: usize, message: String },
+    /// The binary input was written with an unsupported format version.
+    VersionMismatch { expected: u16, found: u16 },
+    /// A row was rejected by the validation of `DataOriented::try_sort`.
+    Validation { row: usize },
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::LengthMismatch { column, expected, found } => {
+                write!(f, "column {} has {} rows but {} were expected", column, found, expected)
+            },
+            Error::SchemaMismatch { expected, found } => {
+                write!(f, "expected the {:?} schema but found {:?}", expected, found)
+            },
+            Error::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            Error::VersionMismatch { expected, found } => {
+                write!(f, "expected the format version {} but found {}", expected, found)
+            },
+            Error::Validation { row } => write!(f, "row {} didn't pass the validation", row),
+            Error::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::DataOriented;
+
+    #[test]
+    fn length_mismatch_from_columns() {
+        let result = DataOriented::from_columns(vec![1, 2], vec![0, 0], vec![3], vec![4, 4], vec![true, false]);
+
+        match result {
+            Err(Error::LengthMismatch { column, expected, found }) => {
+                assert_eq!(column, Column::Attribute);
+                assert_eq!(expected, 2);
+                assert_eq!(found, 1);
+            },
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn schema_mismatch_from_csv() {
+        let csv = "query_index,distance,word_index,attribute,is_exact\n1,2,3,4,true\n";
+        let result = DataOriented::from_csv(csv.as_bytes());
+        assert!(matches!(result, Err(Error::SchemaMismatch { .. })), "{:?}", result);
+    }
+
+    #[test]
+    fn parse_from_csv() {
+        let csv = "query_index,distance,attribute,word_index,is_exact\n1,2,3,4,true\n1,256,3,4,true\n";
+        let result = DataOriented::from_csv(csv.as_bytes());
+        assert!(matches!(result, Err(Error::Parse { line: 3, .. })), "{:?}", result);
+    }
+
+    #[test]
+    fn version_mismatch_from_columns() {
+        let mut bytes = Vec::new();
+        DataOriented::from_seed(42, 10).write_columns(&mut bytes).unwrap();
+        bytes[4] += 1;
+
+        let result = DataOriented::read_columns(&bytes[..]);
+        assert!(matches!(result, Err(Error::VersionMismatch { expected: 1, found: 2 })), "{:?}", result);
+    }
+
+    #[test]
+    fn validation_from_try_sort() {
+        let mut data = DataOriented::from_seed(42, 100).map_distance(|d| d % 8);
+        let original = data.clone();
+        *data.iter_mut().nth(57).unwrap().distance = 200;
+        let poisoned = data.clone();
+
+        match data.try_sort(|row| *row.distance < 8) {
+            Err(Error::Validation { row }) => assert_eq!(row, 57),
+            otherwise => panic!("unexpected result {:?}", otherwise),
+        }
+        assert_eq!(data, poisoned);
+
+        let mut data = original.clone();
+        data.try_sort(|row| *row.distance < 8).unwrap();
+        assert_eq!(data, original.to_sorted());
+    }
+}
@@ -0,0 +1,147 @@
+//! Sorts more rows than fit in memory, through sorted runs written to temporary files,
+//! the rows are read and written in the `DataOriented::to_packed_aos` format.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::process;
+
+use crate::binary::{decode_packed_row, encode_packed_row, invalid_data, PACKED_ROW_LEN};
+use crate::{Classic, DataOriented};
+
+/// The temporary run files, removed when dropped, even on errors.
+struct Runs(Vec<PathBuf>);
+
+impl Drop for Runs {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Creates a run file under a random name in the temporary directory, it is never
+/// opened if it already exists, which could be a symlink planted by another user.
+fn create_run() -> io::Result<(PathBuf, File)> {
+    const ATTEMPTS: usize = 16;
+
+    for _ in 0..ATTEMPTS {
+        let name = format!("data-oriented-sort-{}-{:016x}.run", process::id(), rand::random::<u64>());
+        let path = std::env::temp_dir().join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, "no unused name for a run file"))
+}
+
+fn read_row<R: Read>(reader: &mut R) -> io::Result<Option<Classic>> {
+    let mut bytes = [0; PACKED_ROW_LEN];
+    match reader.read_exact(&mut bytes) {
+        Ok(()) => decode_packed_row(&bytes).map(Some),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Sorts the rows of `input` into `output`, keeping at most `chunk_rows` rows in memory
+/// at once while the sorted runs are written. The runs are then merged with one buffered
+/// reader each, in the temporary directory of the system, equal rows keep their input order.
+///
+/// The input and the output are rows of `PACKED_ROW_LEN` bytes one after the other, as
+/// written by `DataOriented::to_packed_aos`, without any header, and the input length must
+/// be a multiple of `PACKED_ROW_LEN`. A `write_columns` stream can't be used here, it stores
+/// every column whole one after the other, reading its first `chunk_rows` rows would need
+/// to seek into every column. This is also why the runs aren't written by
+/// `sort_and_write_run`: `read_run` loads a whole run and `merge_k_sorted` merges shards
+/// that are all in memory, a run of rows is merged one row at a time instead.
+pub fn external_sort<R: Read, W: Write>(mut input: R, output: W, chunk_rows: usize) -> io::Result<()> {
+    assert!(chunk_rows > 0, "the chunks must contain rows");
+    let chunk_len = chunk_rows
+        .checked_mul(PACKED_ROW_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the chunks are too large"))?;
+
+    // the chunk grows with what is read, a large `chunk_rows` doesn't allocate upfront
+    let mut runs = Runs(Vec::new());
+    let mut chunk = Vec::new();
+    loop {
+        chunk.clear();
+        input.by_ref().take(chunk_len as u64).read_to_end(&mut chunk)?;
+        if chunk.is_empty() {
+            break;
+        }
+        if !chunk.len().is_multiple_of(PACKED_ROW_LEN) {
+            return Err(invalid_data("the input is not a whole number of rows"));
+        }
+
+        let mut data = DataOriented::with_capacity(chunk.len() / PACKED_ROW_LEN);
+        for row in chunk.chunks_exact(PACKED_ROW_LEN) {
+            data.push(decode_packed_row(row)?);
+        }
+        data.sort_unstable();
+
+        let (path, file) = create_run()?;
+        runs.0.push(path);
+        let mut writer = BufWriter::new(file);
+        for row in data.iter() {
+            writer.write_all(&encode_packed_row(row))?;
+        }
+        writer.flush()?;
+    }
+
+    let mut readers = Vec::with_capacity(runs.0.len());
+    let mut heads = BinaryHeap::with_capacity(runs.0.len());
+    for (r, path) in runs.0.iter().enumerate() {
+        let mut reader = BufReader::new(File::open(path)?);
+        if let Some(row) = read_row(&mut reader)? {
+            heads.push(Reverse((row, r)));
+        }
+        readers.push(reader);
+    }
+
+    // the run index breaks the ties, the rows of the first runs come first
+    let mut output = BufWriter::new(output);
+    while let Some(Reverse((row, r))) = heads.pop() {
+        output.write_all(&encode_packed_row(row.as_classic_ref()))?;
+        if let Some(next) = read_row(&mut readers[r])? {
+            heads.push(Reverse((next, r)));
+        }
+    }
+
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_chunks_are_merged_back_sorted() {
+        let data = DataOriented::from_seed(42, 1_000);
+
+        let mut output = Vec::new();
+        external_sort(&data.to_packed_aos()[..], &mut output, 64).unwrap();
+
+        let sorted = DataOriented::from_packed_aos(&output).unwrap();
+        assert_eq!(sorted, data.to_sorted());
+
+        // the last chunk is shorter, an empty input gives an empty output
+        output.clear();
+        external_sort(&data.to_packed_aos()[..10 * 70], &mut output, 64).unwrap();
+        assert_eq!(output.len(), 10 * 70);
+        output.clear();
+        external_sort(&[][..], &mut output, 64).unwrap();
+        assert!(output.is_empty());
+
+        let result = external_sort(&data.to_packed_aos()[..15], &mut output, 64);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let result = external_sort(&[][..], &mut output, usize::MAX);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
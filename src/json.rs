@@ -0,0 +1,113 @@
+use std::convert::TryFrom;
+
+use serde_json::{json, Value};
+
+use crate::{Classic, Column, DataOriented, Error};
+
+impl DataOriented {
+    /// Converts the rows into an array of objects, one per row,
+    /// with one field per column, for the consumers that can't read columns.
+    pub fn to_rows_json(&self) -> Value {
+        self.iter()
+            .map(|row| {
+                json!({
+                    "query_index": row.query_index,
+                    "distance": row.distance,
+                    "attribute": row.attribute,
+                    "word_index": row.word_index,
+                    "is_exact": row.is_exact,
+                })
+            })
+            .collect()
+    }
+
+    /// Reads the rows written by `to_rows_json`, the `line` of a
+    /// `Parse` error is the number of the invalid row, starting at one.
+    pub fn from_rows_json(value: &Value) -> Result<DataOriented, Error> {
+        let rows = value.as_array().ok_or_else(|| Error::SchemaMismatch {
+            expected: "an array of rows".to_string(),
+            found: value.to_string(),
+        })?;
+
+        let mut data = DataOriented::with_capacity(rows.len());
+        for (i, row) in rows.iter().enumerate() {
+            let line = i + 1;
+            let error = |message: String| Error::Parse { line, message };
+            let object = row.as_object().ok_or_else(|| error(format!("{} is not an object", row)))?;
+
+            let integer = |column: Column| {
+                object.get(column.name()).and_then(Value::as_u64).ok_or_else(|| {
+                    error(format!("{} is missing or is not an unsigned integer", column))
+                })
+            };
+            let narrow = |column: Column, value: u64| error(format!("{} {} is out of range", column, value));
+
+            let query_index = integer(Column::QueryIndex)?;
+            let distance = integer(Column::Distance)?;
+            let attribute = integer(Column::Attribute)?;
+            let word_index = integer(Column::WordIndex)?;
+            let is_exact = object.get(Column::IsExact.name()).and_then(Value::as_bool).ok_or_else(|| {
+                error(format!("{} is missing or is not a boolean", Column::IsExact))
+            })?;
+
+            data.push(Classic {
+                query_index: u32::try_from(query_index)
+                    .map_err(|_| narrow(Column::QueryIndex, query_index))?,
+                distance: u8::try_from(distance).map_err(|_| narrow(Column::Distance, distance))?,
+                attribute: u16::try_from(attribute)
+                    .map_err(|_| narrow(Column::Attribute, attribute))?,
+                word_index: u16::try_from(word_index)
+                    .map_err(|_| narrow(Column::WordIndex, word_index))?,
+                is_exact,
+            });
+        }
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_json_shape() {
+        let data =
+            DataOriented::from_columns(vec![3, 1], vec![0, 2], vec![10, 0], vec![7, 65535], vec![true, false])
+                .unwrap();
+
+        let expected = json!([
+            { "query_index": 3, "distance": 0, "attribute": 10, "word_index": 7, "is_exact": true },
+            { "query_index": 1, "distance": 2, "attribute": 0, "word_index": 65535, "is_exact": false },
+        ]);
+        assert_eq!(data.to_rows_json(), expected);
+    }
+
+    #[test]
+    fn rows_json_round_trip() {
+        let data = DataOriented::from_seed(42, 1_000);
+        let json = data.to_rows_json();
+        assert_eq!(DataOriented::from_rows_json(&json).unwrap(), data);
+
+        let text = serde_json::to_string(&json).unwrap();
+        let parsed: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(DataOriented::from_rows_json(&parsed).unwrap(), data);
+    }
+
+    #[test]
+    fn invalid_rows_json() {
+        let result = DataOriented::from_rows_json(&json!({ "query_index": 1 }));
+        assert!(matches!(result, Err(Error::SchemaMismatch { .. })), "{:?}", result);
+
+        let json = json!([
+            { "query_index": 1, "distance": 2, "attribute": 3, "word_index": 4, "is_exact": true },
+            { "query_index": 1, "distance": 256, "attribute": 3, "word_index": 4, "is_exact": true },
+        ]);
+        let result = DataOriented::from_rows_json(&json);
+        assert!(matches!(result, Err(Error::Parse { line: 2, .. })), "{:?}", result);
+
+        let json = json!([{ "query_index": 1, "distance": 2, "attribute": 3, "word_index": 4 }]);
+        let result = DataOriented::from_rows_json(&json);
+        assert!(matches!(result, Err(Error::Parse { line: 1, .. })), "{:?}", result);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{permutations_unstable_by_key, ClassicRef, DataOriented};
+
+type KeyFn<K> = Box<dyn Fn(ClassicRef<'_>) -> K>;
+
+/// A `DataOriented` that remembers how its rows must be sorted,
+/// built with `DataOriented::with_key_fn`.
+pub struct KeyedDataOriented<K> {
+    data: DataOriented,
+    key: KeyFn<K>,
+}
+
+impl DataOriented {
+    /// Stores the key function used by every `sort_unstable` of the returned instance.
+    pub fn with_key_fn<K, F>(self, f: F) -> KeyedDataOriented<K>
+    where F: Fn(ClassicRef<'_>) -> K + 'static,
+          K: Ord,
+    {
+        KeyedDataOriented { data: self, key: Box::new(f) }
+    }
+}
+
+impl<K: Ord> KeyedDataOriented<K> {
+    /// Sorts the rows by the stored key function.
+    pub fn sort_unstable(&mut self) {
+        let data = &self.data;
+        let key = &self.key;
+        let permutations = permutations_unstable_by_key(data.len(), |i| key(data.row(i)));
+        self.data.apply_permutation(&permutations);
+    }
+
+    pub fn get_ref(&self) -> &DataOriented {
+        &self.data
+    }
+
+    pub fn into_inner(self) -> DataOriented {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Reverse;
+
+    use super::*;
+
+    #[test]
+    fn stored_key_sorts_like_sort_unstable_by() {
+        let data = DataOriented::from_seed(42, 1_000);
+
+        let mut expected = data.clone();
+        expected.sort_unstable_by(|a, b| b.distance.cmp(a.distance).then_with(|| a.cmp(&b)));
+
+        let mut keyed = data.with_key_fn(|r| (Reverse(*r.distance), r.to_classic()));
+        keyed.sort_unstable();
+        assert_eq!(keyed.get_ref(), &expected);
+
+        // the key is kept for the following sorts
+        keyed.sort_unstable();
+        assert_eq!(keyed.into_inner(), expected);
+    }
+}
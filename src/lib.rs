@@ -1,21 +1,446 @@
 #![cfg_attr(feature = "nightly", feature(test))]
 
+use std::cmp::Ordering;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, TryReserveError};
 use std::iter::FromIterator;
+use std::mem;
+use std::ops::Range;
+use std::ptr;
+use std::time::{Duration, Instant};
 use std::fmt;
 
+#[cfg(feature = "simd")]
+mod aligned;
+#[cfg(feature = "ndarray")]
+mod array;
+mod binary;
+mod builder;
+mod cache;
+#[cfg(feature = "compact")]
+mod compact;
+mod cow;
+mod csv;
+mod error;
+mod external;
+#[cfg(feature = "serde_json")]
+mod json;
+mod keyed;
+// the files are viewed in place, their columns are written in little-endian
+#[cfg(all(feature = "mmap", target_endian = "little"))]
+mod mmap;
+mod projection;
+mod report;
+#[cfg(feature = "simd")]
+mod simd;
+mod soa;
+mod topk;
+#[cfg(test)]
+mod unsafe_paths;
+mod value;
+mod view;
+
+#[cfg(feature = "simd")]
+pub use self::aligned::{AlignedBuf, AlignedDataOriented, SIMD_ALIGNMENT};
+#[cfg(feature = "compact")]
+pub use self::compact::CompactDataOriented;
+pub use self::binary::{RunMeta, COLUMNS_MAGIC, COLUMNS_VERSION, DELTA_QUERY_INDEX, PACKED_ROW_LEN};
+pub use self::builder::DataOrientedBuilder;
+pub use self::cow::DataOrientedCow;
+pub use self::cache::PermCache;
+pub use self::error::Error;
+pub use self::external::external_sort;
+pub use self::keyed::KeyedDataOriented;
+#[cfg(all(feature = "mmap", target_endian = "little"))]
+pub use self::mmap::MmapDataOriented;
+pub use self::projection::Projection;
+pub use self::report::{Layout, Phase, SortReport, SortStrategy};
+pub use self::soa::SoaSort;
+pub use self::topk::TopK;
+pub use self::value::ColumnValue;
+pub use self::view::DataOrientedViewMut;
+#[cfg(feature = "simd")]
+pub use self::simd::{apply_permutations_u16, apply_permutations_u32};
+
 use rand::{Rng, SeedableRng};
 use rand::distributions::Standard;
 
-#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq)]
-struct Classic {
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// The rng of the seeded generators, the `small_rng` feature trades the
+/// `StdRng` quality and cross-platform reproducibility for speed.
+#[cfg(not(feature = "small_rng"))]
+pub type DefaultRng = rand::rngs::StdRng;
+#[cfg(feature = "small_rng")]
+pub type DefaultRng = rand::rngs::SmallRng;
+
+/// The number of rows generated by each rayon task of `DataOriented::new_par`,
+/// changing it changes the generated rows.
+#[cfg(feature = "rayon")]
+const PAR_CHUNK_LEN: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct Classic {
+    pub query_index: u32,
+    pub distance: u8,
+    pub attribute: u16,
+    pub word_index: u16,
+    pub is_exact: bool,
+}
+
+impl Classic {
+    pub fn packed_key(&self) -> u128 {
+        packed_key(self.query_index, self.distance, self.attribute, self.word_index, self.is_exact)
+    }
+
+    /// Views the fields like a row of a `DataOriented`.
+    pub fn as_classic_ref(&self) -> ClassicRef<'_> {
+        ClassicRef {
+            query_index: &self.query_index,
+            distance: &self.distance,
+            attribute: &self.attribute,
+            word_index: &self.word_index,
+            is_exact: &self.is_exact,
+        }
+    }
+}
+
+/// The position of the lowest bit of each field in the packed keys, the less
+/// significant is the field the lower are its bits, `is_exact` is the last tiebreak.
+pub const IS_EXACT_SHIFT: u32 = 0;
+pub const WORD_INDEX_SHIFT: u32 = IS_EXACT_SHIFT + 1;
+pub const ATTRIBUTE_SHIFT: u32 = WORD_INDEX_SHIFT + u16::BITS;
+pub const DISTANCE_SHIFT: u32 = ATTRIBUTE_SHIFT + u16::BITS;
+pub const QUERY_INDEX_SHIFT: u32 = DISTANCE_SHIFT + u8::BITS;
+/// The number of bits used by the packed keys, the higher ones are zeros.
+pub const PACKED_KEY_BITS: u32 = QUERY_INDEX_SHIFT + u32::BITS;
+
+/// Packs the five fields in a single integer that orders like the fields tuple,
+/// the 73 bits keys are compared in one instruction instead of five.
+pub fn packed_key(query_index: u32, distance: u8, attribute: u16, word_index: u16, is_exact: bool) -> u128 {
+    let order = SortOrder::Ascending;
+    packed_key_with_is_exact_order(query_index, distance, attribute, word_index, is_exact, order)
+}
+
+/// Packs the fields like `packed_key`, with `SortOrder::Descending` the `is_exact`
+/// bit is flipped so that the exact rows come first, like rankings want.
+pub fn packed_key_with_is_exact_order(
+    query_index: u32,
+    distance: u8,
+    attribute: u16,
+    word_index: u16,
+    is_exact: bool,
+    is_exact_order: SortOrder,
+) -> u128 {
+    let orders = [SortOrder::Ascending, SortOrder::Ascending, SortOrder::Ascending, SortOrder::Ascending, is_exact_order];
+    packed_key_with_orders(query_index, distance, attribute, word_index, is_exact, orders)
+}
+
+/// Packs the fields like `packed_key` with one direction per field, in the fields
+/// order, the bits of the descending fields are inverted before being packed so a
+/// single integer comparison gives the mixed order.
+pub fn packed_key_with_orders(
     query_index: u32,
     distance: u8,
     attribute: u16,
     word_index: u16,
     is_exact: bool,
+    orders: [SortOrder; 5],
+) -> u128 {
+    let [q, d, a, w, e] = orders.map(|order| order == SortOrder::Descending);
+    let query_index = if q { !query_index } else { query_index };
+    let distance = if d { !distance } else { distance };
+    let attribute = if a { !attribute } else { attribute };
+    let word_index = if w { !word_index } else { word_index };
+    let is_exact = is_exact ^ e;
+
+    (query_index as u128) << QUERY_INDEX_SHIFT
+        | (distance as u128) << DISTANCE_SHIFT
+        | (attribute as u128) << ATTRIBUTE_SHIFT
+        | (word_index as u128) << WORD_INDEX_SHIFT
+        | (is_exact as u128) << IS_EXACT_SHIFT
+}
+
+/// The number of triples of rows checked by `check_total_order`.
+#[cfg(debug_assertions)]
+const TOTAL_ORDER_SAMPLES: usize = 64;
+
+/// Panics if the comparator of the row indices isn't antisymmetric nor transitive
+/// on a reproducible sample of triples of rows.
+#[cfg(debug_assertions)]
+fn check_total_order<F: FnMut(usize, usize) -> Ordering>(len: usize, mut compare: F) {
+    if len < 2 {
+        return;
+    }
+
+    let mut rng = DefaultRng::seed_from_u64(len as u64);
+    for _ in 0..TOTAL_ORDER_SAMPLES {
+        let [a, b, c] = [(); 3].map(|_| rng.gen_range(0, len));
+
+        let ab = compare(a, b);
+        if compare(b, a) != ab.reverse() {
+            panic!("the comparator is not antisymmetric: rows {} and {} compare {:?} both ways", a, b, ab);
+        }
+
+        let bc = compare(b, c);
+        let ac = compare(a, c);
+        let transitive = |cycle: Ordering| !(ab != cycle && bc != cycle && ac == cycle);
+        if !transitive(Ordering::Greater) || !transitive(Ordering::Less) {
+            panic!(
+                "the comparator is not transitive: rows {}, {} and {} compare {:?}, {:?} but {:?}",
+                a, b, c, ab, bc, ac,
+            );
+        }
+    }
+}
+
+/// Panics if the rows are not sorted, naming the first pair of inverted rows.
+pub fn assert_sorted(data: &DataOriented) {
+    if let Some(i) = (1..data.len()).find(|&i| data.row(i - 1) > data.row(i)) {
+        panic!(
+            "rows {} and {} are not sorted: {:?} > {:?}",
+            i - 1, i, data.row(i - 1).to_classic(), data.row(i).to_classic(),
+        );
+    }
+}
+
+/// Returns the first column whose length differs from the `query_index` one.
+pub(crate) fn check_column_lengths(
+    query_index: &[u32],
+    distance: &[u8],
+    attribute: &[u16],
+    word_index: &[u16],
+    is_exact: &[bool],
+) -> Result<(), Error> {
+    let expected = query_index.len();
+    let lengths = [
+        (Column::Distance, distance.len()),
+        (Column::Attribute, attribute.len()),
+        (Column::WordIndex, word_index.len()),
+        (Column::IsExact, is_exact.len()),
+    ];
+
+    for &(column, found) in &lengths {
+        if found != expected {
+            return Err(Error::LengthMismatch { column, expected, found });
+        }
+    }
+
+    Ok(())
+}
+
+/// Fills `buf` with the indices of the rows of the given columns in sorted order,
+/// this is shared by the different storages of the columns. Returns the strategy used.
+pub(crate) fn argsort_columns(
+    query_index: &[u32],
+    distance: &[u8],
+    attribute: &[u16],
+    word_index: &[u16],
+    is_exact: &[bool],
+    buf: &mut Vec<usize>,
+) -> SortStrategy {
+    let len = query_index.len();
+    assert!(
+        [distance.len(), attribute.len(), word_index.len(), is_exact.len()].iter().all(|&l| l == len),
+        "the columns must have the same length",
+    );
+
+    // the index is the last tiebreak, the keys are therefore unique and the
+    // order of equal rows doesn't depend on the sort algorithm of the std
+    let key = |i: usize| {
+        debug_assert!(i < len);
+        // SAFETY: the indices are generated from `0..len`
+        // and all the columns have the same length.
+        unsafe {
+            (
+                query_index.get_unchecked(i),
+                distance.get_unchecked(i),
+                attribute.get_unchecked(i),
+                word_index.get_unchecked(i),
+                is_exact.get_unchecked(i),
+                i,
+            )
+        }
+    };
+
+    buf.clear();
+    buf.extend(0..len);
+    if len <= INSERTION_SORT_THRESHOLD {
+        insertion_sort_by_key(buf, key);
+        SortStrategy::Insertion
+    } else {
+        buf.sort_unstable_by_key(|&i| key(i));
+        SortStrategy::Comparison
+    }
+}
+
+/// The number of rows under which `sort_auto_layout` sorts a `Vec<Classic>`.
+/// The `auto_layout_*` benchmarks found no crossover, the round trip through the
+/// AoS layout was slower than the permutations, or within the noise, at every
+/// measured size from 1 to 8192 rows, the conversions cost more than what the
+/// contiguous rows save. The AoS layout is therefore never used.
+pub const AUTO_LAYOUT_CROSSOVER: usize = 0;
+
+/// The number of distinct sampled `query_index` under which `sort_auto`
+/// uses a counting sort on this column, see `sort_unstable_two_level`.
+pub const LOW_CARDINALITY_THRESHOLD: usize = 256;
+
+/// The number of first rows from which `sort_auto` estimates the cardinality of
+/// `query_index`, the first rows are cheaper to read than a random sample but
+/// underestimate the cardinality of rows already ordered by `query_index`.
+pub const CARDINALITY_SAMPLE_LEN: usize = 1_024;
+
+/// The number of rows under which the argsorts use an insertion sort,
+/// `tune_insertion_threshold` measures the best value for the current machine.
+pub const INSERTION_SORT_THRESHOLD: usize = 16;
+
+fn insertion_sort_by_key<K: Ord, F: Fn(usize) -> K>(indices: &mut [usize], key: F) {
+    for i in 1..indices.len() {
+        let mut j = i;
+        while j > 0 && key(indices[j - 1]) > key(indices[j]) {
+            indices.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+/// Measures, on this machine, the largest number of rows for which the insertion
+/// sort beats `sort_unstable_by_key`, to calibrate `INSERTION_SORT_THRESHOLD`.
+/// This is a one-time calibration tool that takes some time, not a hot path.
+#[cfg(feature = "nightly")]
+pub fn tune_insertion_threshold() -> usize {
+    use std::hint::black_box;
+    use std::time::{Duration, Instant};
+
+    const CANDIDATES: [usize; 7] = [8, 12, 16, 24, 32, 48, 64];
+    const ROUNDS: usize = 2_000;
+
+    let measure = |data: &DataOriented, f: &dyn Fn(&mut Vec<usize>)| {
+        let mut buf = Vec::with_capacity(data.len());
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            buf.clear();
+            buf.extend(0..data.len());
+            f(black_box(&mut buf));
+        }
+        start.elapsed()
+    };
+
+    let mut threshold = CANDIDATES[0];
+    for &len in &CANDIDATES {
+        let data = DataOriented::from_seed(len as u64, len);
+        let key = |i: usize| data.row(i);
+
+        let (mut insertion, mut pdqsort) = (Duration::default(), Duration::default());
+        // alternate the measures to share the machine noise
+        for _ in 0..5 {
+            insertion += measure(&data, &|buf| insertion_sort_by_key(buf, key));
+            pdqsort += measure(&data, &|buf| buf.sort_unstable_by_key(|&i| key(i)));
+        }
+
+        if insertion <= pdqsort {
+            threshold = len;
+        }
+    }
+
+    threshold
+}
+
+/// Compares two rows in the order of `sort_unstable`, by `query_index`,
+/// `distance`, `attribute`, `word_index` then `is_exact`.
+pub fn compare_rows(a: ClassicRef<'_>, b: ClassicRef<'_>) -> Ordering {
+    a.query_index.cmp(b.query_index)
+        .then_with(|| a.distance.cmp(b.distance))
+        .then_with(|| a.attribute.cmp(b.attribute))
+        .then_with(|| a.word_index.cmp(b.word_index))
+        .then_with(|| a.is_exact.cmp(b.is_exact))
+}
+
+/// Merges two sorted instances into a single sorted one.
+pub fn merge_sorted(a: &DataOriented, b: &DataOriented) -> DataOriented {
+    debug_assert!(a.is_sorted() && b.is_sorted(), "the inputs must be sorted");
+
+    let mut merged = DataOriented::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if compare_rows(b.row(j), a.row(i)) == Ordering::Less {
+            merged.push(b.row(j).to_classic());
+            j += 1;
+        } else {
+            merged.push(a.row(i).to_classic());
+            i += 1;
+        }
+    }
+
+    (i..a.len()).for_each(|i| merged.push(a.row(i).to_classic()));
+    (j..b.len()).for_each(|j| merged.push(b.row(j).to_classic()));
+
+    merged
+}
+
+/// Merges many sorted shards into a single sorted instance in O(N log K),
+/// a heap keeps the smallest head row of every shard on top.
+pub fn merge_k_sorted(shards: Vec<DataOriented>) -> DataOriented {
+    debug_assert!(shards.iter().all(DataOriented::is_sorted), "the shards must be sorted");
+
+    let len = shards.iter().map(DataOriented::len).sum();
+    let mut merged = DataOriented::with_capacity(len);
+
+    let mut heads = BinaryHeap::with_capacity(shards.len());
+    for (s, shard) in shards.iter().enumerate() {
+        if let Some(row) = shard.get(0) {
+            heads.push(Reverse((row, s, 0)));
+        }
+    }
+
+    while let Some(Reverse((row, s, i))) = heads.pop() {
+        merged.push(row.to_classic());
+        if let Some(next) = shards[s].get(i + 1) {
+            heads.push(Reverse((next, s, i + 1)));
+        }
+    }
+
+    merged
+}
+
+/// Merge sorts the keys, using the scratch buffer, and returns the number of
+/// inversions, every time a right key is merged before the rest of the left ones.
+fn count_inversions(keys: &mut [u128], scratch: &mut [u128]) -> usize {
+    if keys.len() < 2 {
+        return 0;
+    }
+
+    let mid = keys.len() / 2;
+    let mut count = count_inversions(&mut keys[..mid], &mut scratch[..mid])
+        + count_inversions(&mut keys[mid..], &mut scratch[mid..]);
+
+    let (left, right) = keys.split_at(mid);
+    let (mut i, mut j) = (0, 0);
+    for slot in scratch.iter_mut().take(left.len() + right.len()) {
+        if j == right.len() || (i < left.len() && left[i] <= right[j]) {
+            *slot = left[i];
+            i += 1;
+        } else {
+            *slot = right[j];
+            count += left.len() - i;
+            j += 1;
+        }
+    }
+
+    let len = keys.len();
+    keys.copy_from_slice(&scratch[..len]);
+    count
+}
+
+/// Compares two keys by computing the ordering instead of branching on it.
+fn branchless_cmp(a: u128, b: u128) -> Ordering {
+    let ordering = (a > b) as i8 - (a < b) as i8;
+    // SAFETY: `Ordering` is `repr(i8)` with -1, 0 and 1 as discriminants.
+    unsafe { std::mem::transmute::<i8, Ordering>(ordering) }
 }
 
-fn new_classics<R: Rng + Clone>(rng: R, len: usize) -> Vec<Classic> {
+pub fn new_classics<R: Rng + Clone>(rng: R, len: usize) -> Vec<Classic> {
     let mut query_index = rng.clone().sample_iter(Standard);
     let mut distance = rng.clone().sample_iter(Standard);
     let mut attribute = rng.clone().sample_iter(Standard);
@@ -38,8 +463,191 @@ fn new_classics<R: Rng + Clone>(rng: R, len: usize) -> Vec<Classic> {
     classics
 }
 
-#[derive(Clone, PartialOrd, Ord, PartialEq, Eq)]
-struct DataOriented {
+/// Sorts the structures like `sort_unstable` with a least significant digit radix
+/// sort over the same digits as `DataOriented::radix_sort`, moving whole structures
+/// at each pass instead of indices, for an AoS against SoA radix comparison.
+pub fn radix_sort_classics(v: &mut Vec<Classic>) {
+    fn pass<F: Fn(&Classic) -> usize>(src: &[Classic], dst: &mut [Classic], buckets: usize, digit: F) {
+        let mut offsets = vec![0; buckets + 1];
+        for c in src {
+            offsets[digit(c) + 1] += 1;
+        }
+        for b in 1..offsets.len() {
+            offsets[b] += offsets[b - 1];
+        }
+        for c in src {
+            let offset = &mut offsets[digit(c)];
+            dst[*offset] = c.clone();
+            *offset += 1;
+        }
+    }
+
+    let mut dst = v.clone();
+    let mut sort_by = |buckets: usize, digit: &dyn Fn(&Classic) -> usize| {
+        pass(v, &mut dst, buckets, digit);
+        std::mem::swap(v, &mut dst);
+    };
+
+    // from the least significant field to the most significant one
+    sort_by(2, &|c| c.is_exact as usize);
+    sort_by(1 << 16, &|c| c.word_index as usize);
+    sort_by(1 << 16, &|c| c.attribute as usize);
+    sort_by(256, &|c| c.distance as usize);
+    for shift in &[0, 8, 16, 24] {
+        sort_by(256, &|c| (c.query_index >> shift) as usize & 0xFF);
+    }
+}
+
+/// The columns of a `DataOriented`, in sort priority order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Column {
+    QueryIndex,
+    Distance,
+    Attribute,
+    WordIndex,
+    IsExact,
+}
+
+impl Column {
+    pub const ALL: [Column; 5] = [
+        Column::QueryIndex,
+        Column::Distance,
+        Column::Attribute,
+        Column::WordIndex,
+        Column::IsExact,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::QueryIndex => "query_index",
+            Column::Distance => "distance",
+            Column::Attribute => "attribute",
+            Column::WordIndex => "word_index",
+            Column::IsExact => "is_exact",
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The correlations between the generated columns of `DataOriented::new_correlated`,
+/// distances are drawn in `0..=max_distance` and `is_exact` depends on them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Correlations {
+    pub max_distance: u8,
+    /// The probability of `is_exact` being true when the distance is zero.
+    pub exact_when_zero_distance: f64,
+    /// The probability of `is_exact` being true for the other distances.
+    pub exact_otherwise: f64,
+}
+
+impl Default for Correlations {
+    fn default() -> Correlations {
+        Correlations { max_distance: 4, exact_when_zero_distance: 0.9, exact_otherwise: 0.05 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+impl SortOrder {
+    fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            SortOrder::Ascending => ordering,
+            SortOrder::Descending => ordering.reverse(),
+        }
+    }
+}
+
+/// Where the missing values of a nullable column are ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NullOrder {
+    First,
+    Last,
+}
+
+/// A view of one row of a `DataOriented`, fields order matches `Classic`.
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
+pub struct ClassicRef<'a> {
+    pub query_index: &'a u32,
+    pub distance: &'a u8,
+    pub attribute: &'a u16,
+    pub word_index: &'a u16,
+    pub is_exact: &'a bool,
+}
+
+impl ClassicRef<'_> {
+    pub fn to_classic(&self) -> Classic {
+        Classic {
+            query_index: *self.query_index,
+            distance: *self.distance,
+            attribute: *self.attribute,
+            word_index: *self.word_index,
+            is_exact: *self.is_exact,
+        }
+    }
+}
+
+// The owned rows compare with the views without being converted,
+// for example against a sentinel while merging.
+
+impl PartialEq<Classic> for ClassicRef<'_> {
+    fn eq(&self, other: &Classic) -> bool {
+        *self == other.as_classic_ref()
+    }
+}
+
+impl PartialEq<ClassicRef<'_>> for Classic {
+    fn eq(&self, other: &ClassicRef<'_>) -> bool {
+        self.as_classic_ref() == *other
+    }
+}
+
+impl PartialOrd<Classic> for ClassicRef<'_> {
+    fn partial_cmp(&self, other: &Classic) -> Option<Ordering> {
+        Some(compare_rows(*self, other.as_classic_ref()))
+    }
+}
+
+impl PartialOrd<ClassicRef<'_>> for Classic {
+    fn partial_cmp(&self, other: &ClassicRef<'_>) -> Option<Ordering> {
+        Some(compare_rows(self.as_classic_ref(), *other))
+    }
+}
+
+/// A mutable view of one row of a `DataOriented`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ClassicMut<'a> {
+    pub query_index: &'a mut u32,
+    pub distance: &'a mut u8,
+    pub attribute: &'a mut u16,
+    pub word_index: &'a mut u16,
+    pub is_exact: &'a mut bool,
+}
+
+macro_rules! column_mapper {
+    ($(#[$doc:meta])* $name:ident, $field:ident: $t:ty) => {
+        $(#[$doc])*
+        pub fn $name<F: Fn($t) -> $t>(&self, f: F) -> DataOriented {
+            let mut data = self.clone();
+            data.$field.iter_mut().for_each(|value| *value = f(*value));
+            data
+        }
+    };
+}
+
+// The derived `Eq` and `Hash` both work column by column, but as the columns
+// always have the same length two datasets are equal (and hash the same)
+// exactly when they contain the same rows in the same order.
+#[derive(Default, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct DataOriented {
     query_index: Vec<u32>,
     distance: Vec<u8>,
     attribute: Vec<u16>,
@@ -47,6 +655,74 @@ struct DataOriented {
     is_exact: Vec<bool>,
 }
 
+impl Clone for DataOriented {
+    fn clone(&self) -> DataOriented {
+        DataOriented {
+            query_index: self.query_index.clone(),
+            distance: self.distance.clone(),
+            attribute: self.attribute.clone(),
+            word_index: self.word_index.clone(),
+            is_exact: self.is_exact.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &DataOriented) {
+        source.clone_into(self);
+    }
+}
+
+impl Extend<Classic> for DataOriented {
+    fn extend<I: IntoIterator<Item = Classic>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        iter.for_each(|row| self.push(row));
+    }
+}
+
+impl FromIterator<Classic> for DataOriented {
+    fn from_iter<I: IntoIterator<Item = Classic>>(iter: I) -> DataOriented {
+        let mut data = DataOriented::default();
+        data.extend(iter);
+        data
+    }
+}
+
+/// Each rayon task collects its own rows and the instances are appended
+/// in order, indexed parallel iterators give the same rows as the sequential collect.
+#[cfg(feature = "rayon")]
+impl FromParallelIterator<Classic> for DataOriented {
+    fn from_par_iter<I: IntoParallelIterator<Item = Classic>>(par_iter: I) -> DataOriented {
+        par_iter
+            .into_par_iter()
+            .fold(DataOriented::default, |mut data, row| {
+                data.push(row);
+                data
+            })
+            .reduce(DataOriented::default, |mut left, mut right| {
+                left.append(&mut right);
+                left
+            })
+    }
+}
+
+impl PartialEq<[Classic]> for DataOriented {
+    fn eq(&self, other: &[Classic]) -> bool {
+        self.validate_against(other).is_ok()
+    }
+}
+
+impl PartialEq<&[Classic]> for DataOriented {
+    fn eq(&self, other: &&[Classic]) -> bool {
+        *self == **other
+    }
+}
+
+impl PartialEq<Vec<Classic>> for DataOriented {
+    fn eq(&self, other: &Vec<Classic>) -> bool {
+        *self == other[..]
+    }
+}
+
 impl fmt::Debug for DataOriented {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         writeln!(fmt, "DataOriented {{")?;
@@ -62,7 +738,7 @@ impl fmt::Debug for DataOriented {
 }
 
 impl DataOriented {
-    fn new<R: Rng + Clone>(rng: R, len: usize) -> DataOriented {
+    pub fn new<R: Rng + Clone>(rng: R, len: usize) -> DataOriented {
         let query_index = rng.clone().sample_iter(Standard).take(len);
         let distance = rng.clone().sample_iter(Standard).take(len);
         let attribute = rng.clone().sample_iter(Standard).take(len);
@@ -78,136 +754,3747 @@ impl DataOriented {
         }
     }
 
-    fn len(&self) -> usize {
-        self.query_index.len()
+    /// Builds an instance from its columns, they must all have the same length.
+    pub fn from_columns(
+        query_index: Vec<u32>,
+        distance: Vec<u8>,
+        attribute: Vec<u16>,
+        word_index: Vec<u16>,
+        is_exact: Vec<bool>,
+    ) -> Result<DataOriented, Error> {
+        check_column_lengths(&query_index, &distance, &attribute, &word_index, &is_exact)?;
+        Ok(DataOriented { query_index, distance, attribute, word_index, is_exact })
     }
-}
-
-fn permutations_unstable_by_key<F, K>(len: usize, mut f: F) -> Vec<usize>
-where F: FnMut(usize) -> K,
-      K: Ord,
-{
-    let mut permutations: Vec<usize> = (0..len).collect();
-    permutations.sort_unstable_by_key(|&i| f(i));
-    permutations
-}
-
-// this function is O(N) in term of memory but it could be O(1)
-// by following this blog post
-// https://devblogs.microsoft.com/oldnewthing/20170102-00/?p=95095
-fn apply_permutations<T: Clone>(permutations: &[usize], vec: &mut Vec<T>) {
-    assert_eq!(permutations.len(), vec.len());
 
-    // it is not necessary to restrict items to be Clone,
-    // we could ptr::read and, after having copied everything,
-    // set_len to 0 and drop the "empty" vec.
-    let mut new = Vec::with_capacity(permutations.len());
-    for &i in permutations {
-        let elem = unsafe { vec.get_unchecked(i) };
-        new.push(elem.clone());
+    /// Generates `len` rows from a `DefaultRng` seeded with `seed`.
+    pub fn from_seed(seed: u64, len: usize) -> DataOriented {
+        DataOriented::new(DefaultRng::seed_from_u64(seed), len)
     }
-    std::mem::replace(vec, new);
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Collects the rows in sorted order by inserting them in a `BTreeMap`
+    /// counting the duplicated rows, the order is ready at the end of the stream.
+    ///
+    /// Every insertion costs a tree descent and a node may be allocated every
+    /// few rows, collecting then sorting is faster when every row is distinct,
+    /// this is only worth it when the stream has many duplicates.
+    pub fn from_iter_sorted<I: IntoIterator<Item = Classic>>(iter: I) -> DataOriented {
+        let mut counts = BTreeMap::new();
+        for row in iter {
+            *counts.entry(row).or_insert(0usize) += 1;
+        }
 
-    use rand::rngs::StdRng;
+        let mut data = DataOriented::with_capacity(counts.values().sum());
+        for (row, count) in counts {
+            (0..count).for_each(|_| data.push(row.clone()));
+        }
+        data
+    }
 
-    #[test]
-    fn data_oriented_sort_is_valid() {
-        let length = 16_000;
+    /// Generates the `from_seed` rows sorted in descending order.
+    pub fn reverse_sorted(seed: u64, len: usize) -> DataOriented {
+        let mut data = DataOriented::from_seed(seed, len);
+        data.sort_unstable();
+        data.reverse();
+        data
+    }
 
-        let rng = StdRng::from_seed([42; 32]);
-        let mut classics = new_classics(rng, length);
+    /// Generates `len` rows where the distance and `is_exact` columns
+    /// are correlated, as they are in real ranking data.
+    pub fn new_correlated(seed: u64, len: usize, correlations: Correlations) -> DataOriented {
+        let mut rng = DefaultRng::seed_from_u64(seed);
+        let mut data = DataOriented::with_capacity(len);
 
-        let rng = StdRng::from_seed([42; 32]);
-        let mut data_oriented = DataOriented::new(rng, length);
+        for _ in 0..len {
+            let distance = rng.gen_range(0, correlations.max_distance as u16 + 1) as u8;
+            let probability = if distance == 0 {
+                correlations.exact_when_zero_distance
+            } else {
+                correlations.exact_otherwise
+            };
 
-        // before sort
-        for i in 0..length {
-            let classic = &classics[i];
-            assert_eq!(classic.query_index, data_oriented.query_index[i]);
-            assert_eq!(classic.distance,    data_oriented.distance[i]);
-            assert_eq!(classic.attribute,   data_oriented.attribute[i]);
-            assert_eq!(classic.word_index,  data_oriented.word_index[i]);
-            assert_eq!(classic.is_exact,    data_oriented.is_exact[i]);
+            data.push(Classic {
+                query_index: rng.gen(),
+                distance,
+                attribute: rng.gen(),
+                word_index: rng.gen(),
+                is_exact: rng.gen_bool(probability),
+            });
         }
 
-        // sort classics
-        classics.sort_unstable();
+        data
+    }
 
-        // sort data oriented
-        let permutations = permutations_unstable_by_key(data_oriented.len(), |i| unsafe {
-            (
-                data_oriented.query_index.get_unchecked(i),
-                data_oriented.distance.get_unchecked(i),
-                data_oriented.attribute.get_unchecked(i),
-                data_oriented.word_index.get_unchecked(i),
-                data_oriented.is_exact.get_unchecked(i),
-            )
-        });
+    /// Generates `len` rows in parallel, chunks of rows are generated by
+    /// rngs seeded from `seed` and the chunk index, the output is therefore
+    /// the same whatever the number of threads, but differs from `from_seed`.
+    #[cfg(feature = "rayon")]
+    pub fn new_par(seed: u64, len: usize) -> DataOriented {
+        let chunks_count = len.div_ceil(PAR_CHUNK_LEN);
+        let chunks: Vec<_> = (0..chunks_count)
+            .into_par_iter()
+            .map(|chunk| {
+                let start = chunk * PAR_CHUNK_LEN;
+                let chunk_len = PAR_CHUNK_LEN.min(len - start);
+                let chunk_seed = seed ^ (chunk as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                DataOriented::from_seed(chunk_seed, chunk_len)
+            })
+            .collect();
 
-        apply_permutations(&permutations, &mut data_oriented.query_index);
-        apply_permutations(&permutations, &mut data_oriented.distance);
-        apply_permutations(&permutations, &mut data_oriented.attribute);
-        apply_permutations(&permutations, &mut data_oriented.word_index);
-        apply_permutations(&permutations, &mut data_oriented.is_exact);
+        let mut data = DataOriented::with_capacity(len);
+        for mut chunk in chunks {
+            data.append(&mut chunk);
+        }
+        data
+    }
 
-        // after sort
-        for i in 0..length {
-            let classic = &classics[i];
-            assert_eq!(classic.query_index, data_oriented.query_index[i]);
-            assert_eq!(classic.distance,    data_oriented.distance[i]);
-            assert_eq!(classic.attribute,   data_oriented.attribute[i]);
-            assert_eq!(classic.word_index,  data_oriented.word_index[i]);
-            assert_eq!(classic.is_exact,    data_oriented.is_exact[i]);
+    pub fn with_capacity(capacity: usize) -> DataOriented {
+        DataOriented {
+            query_index: Vec::with_capacity(capacity),
+            distance: Vec::with_capacity(capacity),
+            attribute: Vec::with_capacity(capacity),
+            word_index: Vec::with_capacity(capacity),
+            is_exact: Vec::with_capacity(capacity),
         }
     }
-}
 
-#[cfg(all(feature = "nightly", test))]
-mod bench {
-    extern crate test;
+    /// Like `with_capacity` but returns an error instead of aborting
+    /// when the columns can't be allocated.
+    pub fn try_with_capacity(capacity: usize) -> Result<DataOriented, TryReserveError> {
+        let mut data = DataOriented::default();
+        data.try_reserve(capacity)?;
+        Ok(data)
+    }
 
-    use super::*;
+    pub fn push(&mut self, row: Classic) {
+        self.query_index.push(row.query_index);
+        self.distance.push(row.distance);
+        self.attribute.push(row.attribute);
+        self.word_index.push(row.word_index);
+        self.is_exact.push(row.is_exact);
+    }
 
-    use rand::SeedableRng;
-    use rand::rngs::StdRng;
+    /// Appends the rows like `extend` but fills one column at a time, each
+    /// column is reserved once and filled from a simple loop over the slice.
+    pub fn extend_from_classics_slice(&mut self, items: &[Classic]) {
+        self.query_index.extend(items.iter().map(|c| c.query_index));
+        self.distance.extend(items.iter().map(|c| c.distance));
+        self.attribute.extend(items.iter().map(|c| c.attribute));
+        self.word_index.extend(items.iter().map(|c| c.word_index));
+        self.is_exact.extend(items.iter().map(|c| c.is_exact));
+    }
 
-    #[bench]
-    fn classics_16_000(b: &mut test::Bencher) {
-        let rng = StdRng::from_seed([42; 32]);
-        let data = new_classics(rng, 16_000);
+    /// Swaps two rows, in all the columns.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.query_index.swap(a, b);
+        self.distance.swap(a, b);
+        self.attribute.swap(a, b);
+        self.word_index.swap(a, b);
+        self.is_exact.swap(a, b);
+    }
 
-        b.iter(|| {
-            data.clone().sort_unstable();
-        })
+    pub fn from_classics(classics: Vec<Classic>) -> DataOriented {
+        classics.into_iter().collect()
     }
 
-    #[bench]
+    pub fn into_classics(self) -> Vec<Classic> {
+        self.iter().map(|r| r.to_classic()).collect()
+    }
+
+    /// Sorts the rows like `sort_unstable`, through a `Vec<Classic>` when there
+    /// are fewer rows than `AUTO_LAYOUT_CROSSOVER` and the permutations otherwise.
+    /// Returns which of the two was used.
+    pub fn sort_auto_layout(&mut self) -> SortReport {
+        let start = Instant::now();
+        // the crossover is zero until the benchmarks find one
+        #[allow(clippy::absurd_extreme_comparisons)]
+        let (strategy, layout) = if self.len() < AUTO_LAYOUT_CROSSOVER {
+            let mut classics = std::mem::take(self).into_classics();
+            classics.sort_unstable();
+            *self = DataOriented::from_classics(classics);
+            (SortStrategy::Comparison, Layout::Aos)
+        } else {
+            (self.argsort_and_apply(), Layout::Soa)
+        };
+
+        SortReport { strategy, layout, elapsed_hint: Some(start.elapsed()) }
+    }
+
+    /// Sorts the rows like `sort_unstable` with the strategy that suits them: the
+    /// distinct values of `query_index` are counted in the `CARDINALITY_SAMPLE_LEN`
+    /// first rows and, when there are at most `LOW_CARDINALITY_THRESHOLD` of them,
+    /// a counting sort on this column only leaves small buckets to be compared.
+    /// A wrong estimation only costs time, the rows are sorted either way.
+    pub fn sort_auto(&mut self) -> SortReport {
+        let start = Instant::now();
+
+        let low_cardinality = self.len() > INSERTION_SORT_THRESHOLD && {
+            let mut sample = self.query_index[..self.len().min(CARDINALITY_SAMPLE_LEN)].to_vec();
+            sample.sort_unstable();
+            sample.dedup();
+            sample.len() <= LOW_CARDINALITY_THRESHOLD
+        };
+
+        let strategy = if low_cardinality {
+            self.sort_unstable_two_level();
+            SortStrategy::Counting
+        } else {
+            self.argsort_and_apply()
+        };
+
+        SortReport { strategy, layout: Layout::Soa, elapsed_hint: Some(start.elapsed()) }
+    }
+
+    /// Argsorts the rows and applies the permutations, returns the strategy of the argsort.
+    fn argsort_and_apply(&mut self) -> SortStrategy {
+        let mut permutations = Vec::new();
+        let strategy = argsort_columns(
+            &self.query_index,
+            &self.distance,
+            &self.attribute,
+            &self.word_index,
+            &self.is_exact,
+            &mut permutations,
+        );
+        self.apply_permutation(&permutations);
+        strategy
+    }
+
+    /// Rotates the rows so that the row `mid` becomes the first one.
+    pub fn rotate_left(&mut self, mid: usize) {
+        self.query_index.rotate_left(mid);
+        self.distance.rotate_left(mid);
+        self.attribute.rotate_left(mid);
+        self.word_index.rotate_left(mid);
+        self.is_exact.rotate_left(mid);
+    }
+
+    /// Rotates the rows so that the last `k` rows become the first ones.
+    pub fn rotate_right(&mut self, k: usize) {
+        self.query_index.rotate_right(k);
+        self.distance.rotate_right(k);
+        self.attribute.rotate_right(k);
+        self.word_index.rotate_right(k);
+        self.is_exact.rotate_right(k);
+    }
+
+    /// Copies the rows into `dst`, reusing its columns allocations,
+    /// nothing is allocated when `dst` has the capacity for the rows.
+    pub fn clone_into(&self, dst: &mut DataOriented) {
+        dst.clear();
+        dst.query_index.extend_from_slice(&self.query_index);
+        dst.distance.extend_from_slice(&self.distance);
+        dst.attribute.extend_from_slice(&self.attribute);
+        dst.word_index.extend_from_slice(&self.word_index);
+        dst.is_exact.extend_from_slice(&self.is_exact);
+    }
+
+    /// Reverses the order of the rows.
+    pub fn reverse(&mut self) {
+        self.query_index.reverse();
+        self.distance.reverse();
+        self.attribute.reverse();
+        self.word_index.reverse();
+        self.is_exact.reverse();
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.query_index.reserve(additional);
+        self.distance.reserve(additional);
+        self.attribute.reserve(additional);
+        self.word_index.reserve(additional);
+        self.is_exact.reserve(additional);
+    }
+
+    /// Like `reserve` but returns an error instead of aborting when a column
+    /// can't grow, the columns reserved before the failing one are shrunk back.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.try_reserve_columns(|data, column| match column {
+            Column::QueryIndex => data.query_index.try_reserve(additional),
+            Column::Distance => data.distance.try_reserve(additional),
+            Column::Attribute => data.attribute.try_reserve(additional),
+            Column::WordIndex => data.word_index.try_reserve(additional),
+            Column::IsExact => data.is_exact.try_reserve(additional),
+        })
+    }
+
+    /// Reserves the columns with `reserve`, one after the other in the `Column::ALL`
+    /// order, and shrinks them back to their previous capacities when one fails.
+    fn try_reserve_columns<F>(&mut self, mut reserve: F) -> Result<(), TryReserveError>
+    where F: FnMut(&mut DataOriented, Column) -> Result<(), TryReserveError>,
+    {
+        let capacities = self.capacities();
+        let result = Column::ALL.iter().try_for_each(|&column| reserve(self, column));
+
+        if result.is_err() {
+            self.query_index.shrink_to(capacities[0]);
+            self.distance.shrink_to(capacities[1]);
+            self.attribute.shrink_to(capacities[2]);
+            self.word_index.shrink_to(capacities[3]);
+            self.is_exact.shrink_to(capacities[4]);
+        }
+
+        result
+    }
+
+    /// The capacities of the columns, in declaration order.
+    fn capacities(&self) -> [usize; 5] {
+        [
+            self.query_index.capacity(),
+            self.distance.capacity(),
+            self.attribute.capacity(),
+            self.word_index.capacity(),
+            self.is_exact.capacity(),
+        ]
+    }
+
+    pub fn clear(&mut self) {
+        self.query_index.clear();
+        self.distance.clear();
+        self.attribute.clear();
+        self.word_index.clear();
+        self.is_exact.clear();
+    }
+
+    /// Moves all the rows of `other` at the end of `self`, leaving `other` empty.
+    pub fn append(&mut self, other: &mut DataOriented) {
+        self.query_index.append(&mut other.query_index);
+        self.distance.append(&mut other.distance);
+        self.attribute.append(&mut other.attribute);
+        self.word_index.append(&mut other.word_index);
+        self.is_exact.append(&mut other.is_exact);
+    }
+
+    /// Removes the rows of the range and yields them, like `Vec::drain` the rows
+    /// are removed from all the columns even if the iterator is not consumed.
+    pub fn drain(&mut self, range: Range<usize>) -> impl Iterator<Item = Classic> + '_ {
+        self.query_index
+            .drain(range.clone())
+            .zip(self.distance.drain(range.clone()))
+            .zip(self.attribute.drain(range.clone()))
+            .zip(self.word_index.drain(range.clone()))
+            .zip(self.is_exact.drain(range))
+            .map(|((((query_index, distance), attribute), word_index), is_exact)| {
+                Classic { query_index, distance, attribute, word_index, is_exact }
+            })
+    }
+
+    pub fn len(&self) -> usize {
+        self.query_index.len()
+    }
+
+    pub fn query_index(&self) -> &[u32] {
+        &self.query_index
+    }
+
+    pub fn distance(&self) -> &[u8] {
+        &self.distance
+    }
+
+    pub fn attribute(&self) -> &[u16] {
+        &self.attribute
+    }
+
+    pub fn word_index(&self) -> &[u16] {
+        &self.word_index
+    }
+
+    pub fn is_exact(&self) -> &[bool] {
+        &self.is_exact
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<ClassicRef<'_>> {
+        if index < self.len() {
+            Some(self.row(index))
+        } else {
+            None
+        }
+    }
+
+    // called from the comparators of most sorts, it is inlined without any
+    // hint, see the `row_key_*` benchmarks
+    fn row(&self, index: usize) -> ClassicRef<'_> {
+        ClassicRef {
+            query_index: &self.query_index[index],
+            distance: &self.distance[index],
+            attribute: &self.attribute[index],
+            word_index: &self.word_index[index],
+            is_exact: &self.is_exact[index],
+        }
+    }
+
+    /// Iterates over the pairs of consecutive rows, like `slice::windows(2)`,
+    /// `find` the first pair out of order to locate an inversion.
+    pub fn adjacent_pairs(&self) -> impl Iterator<Item = (ClassicRef<'_>, ClassicRef<'_>)> + '_ {
+        (1..self.len()).map(move |i| (self.row(i - 1), self.row(i)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ClassicRef<'_>> + '_ {
+        self.query_index.iter()
+            .zip(&self.distance)
+            .zip(&self.attribute)
+            .zip(&self.word_index)
+            .zip(&self.is_exact)
+            .map(|((((query_index, distance), attribute), word_index), is_exact)| {
+                ClassicRef { query_index, distance, attribute, word_index, is_exact }
+            })
+    }
+
+    /// Iterates over the rows in parallel, by zipping the columns.
+    #[cfg(feature = "rayon")]
+    pub fn par_iter(&self) -> impl IndexedParallelIterator<Item = ClassicRef<'_>> + '_ {
+        self.query_index.par_iter()
+            .zip(&self.distance)
+            .zip(&self.attribute)
+            .zip(&self.word_index)
+            .zip(&self.is_exact)
+            .map(|((((query_index, distance), attribute), word_index), is_exact)| {
+                ClassicRef { query_index, distance, attribute, word_index, is_exact }
+            })
+    }
+
+    /// Iterates over the rows, the columns are borrowed separately
+    /// and can't be desynchronized by the mutations.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = ClassicMut<'_>> + '_ {
+        self.query_index.iter_mut()
+            .zip(&mut self.distance)
+            .zip(&mut self.attribute)
+            .zip(&mut self.word_index)
+            .zip(&mut self.is_exact)
+            .map(|((((query_index, distance), attribute), word_index), is_exact)| {
+                ClassicMut { query_index, distance, attribute, word_index, is_exact }
+            })
+    }
+
+    /// Returns whether both instances contain the same rows in the same order,
+    /// comparing whole columns at a time, capacities are ignored.
+    pub fn rows_eq(&self, other: &DataOriented) -> bool {
+        self.len() == other.len()
+            && self.query_index == other.query_index
+            && self.distance == other.distance
+            && self.attribute == other.attribute
+            && self.word_index == other.word_index
+            && self.is_exact == other.is_exact
+    }
+
+    /// Returns the index of the first row that differs between both instances,
+    /// when one is a prefix of the other the length of the shortest is returned.
+    pub fn first_difference(&self, other: &DataOriented) -> Option<usize> {
+        let common = self.len().min(other.len());
+        match (0..common).find(|&i| self.row(i) != other.row(i)) {
+            Some(i) => Some(i),
+            None if self.len() != other.len() => Some(common),
+            None => None,
+        }
+    }
+
+    /// Checks that the columns all have the same number of rows, an instance
+    /// that fails it can only come from a bug of a constructor.
+    pub fn check_invariants(&self) -> Result<(), Error> {
+        check_column_lengths(
+            &self.query_index,
+            &self.distance,
+            &self.attribute,
+            &self.word_index,
+            &self.is_exact,
+        )
+    }
+
+    /// Checks that the rows are equal to the `expected` ones, returns
+    /// the first mismatching row and column. When the lengths differ the first
+    /// row that only one side has is reported on the `query_index` column.
+    pub fn validate_against(&self, expected: &[Classic]) -> Result<(), (usize, Column)> {
+        for (i, (row, classic)) in self.iter().zip(expected).enumerate() {
+            let mismatch = if *row.query_index != classic.query_index {
+                Some(Column::QueryIndex)
+            } else if *row.distance != classic.distance {
+                Some(Column::Distance)
+            } else if *row.attribute != classic.attribute {
+                Some(Column::Attribute)
+            } else if *row.word_index != classic.word_index {
+                Some(Column::WordIndex)
+            } else if *row.is_exact != classic.is_exact {
+                Some(Column::IsExact)
+            } else {
+                None
+            };
+
+            if let Some(column) = mismatch {
+                return Err((i, column));
+            }
+        }
+
+        if self.len() != expected.len() {
+            return Err((self.len().min(expected.len()), Column::QueryIndex));
+        }
+
+        Ok(())
+    }
+
+    /// Counts the pairs of rows that are out of order, in O(N log N)
+    /// by counting them while merge sorting the packed keys.
+    pub fn inversions_by_key(&self) -> usize {
+        let mut keys = self.packed_keys();
+        let mut scratch = keys.clone();
+        count_inversions(&mut keys, &mut scratch)
+    }
+
+    pub fn is_sorted(&self) -> bool {
+        (1..self.len()).all(|i| compare_rows(self.row(i - 1), self.row(i)) != Ordering::Greater)
+    }
+
+    /// Returns the maximal runs of rows already sorted in ascending order, the
+    /// runs follow each other and cover all the rows, for a merge sort to start from.
+    pub fn natural_runs(&self) -> Vec<Range<usize>> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        for i in 1..self.len() {
+            if compare_rows(self.row(i - 1), self.row(i)) == Ordering::Greater {
+                runs.push(start..i);
+                start = i;
+            }
+        }
+        if !self.is_empty() {
+            runs.push(start..self.len());
+        }
+        runs
+    }
+
+    /// Returns every distinct row with its number of occurrences, in ascending
+    /// order, the rows themselves are not moved.
+    pub fn row_frequencies(&self) -> Vec<(Classic, usize)> {
+        let mut frequencies: Vec<(Classic, usize)> = Vec::new();
+        for i in self.argsort_unstable() {
+            match frequencies.last_mut() {
+                Some((row, count)) if self.row(i) == *row => *count += 1,
+                _ => frequencies.push((self.row(i).to_classic(), 1)),
+            }
+        }
+        frequencies
+    }
+
+    column_mapper!(
+        /// Returns a copy where the `query_index` column is transformed by `f`.
+        map_query_index, query_index: u32
+    );
+    column_mapper!(
+        /// Returns a copy where the `distance` column is transformed by `f`.
+        map_distance, distance: u8
+    );
+    column_mapper!(
+        /// Returns a copy where the `attribute` column is transformed by `f`.
+        map_attribute, attribute: u16
+    );
+    column_mapper!(
+        /// Returns a copy where the `word_index` column is transformed by `f`.
+        map_word_index, word_index: u16
+    );
+    column_mapper!(
+        /// Returns a copy where the `is_exact` column is transformed by `f`.
+        map_is_exact, is_exact: bool
+    );
+
+    /// Returns a 64 bits FNV-1a hash of the row count and of the columns, in
+    /// little-endian and in the fields order. Unlike `Hash`, which depends on the
+    /// hasher, it is the same across runs, builds and platforms.
+    pub fn fingerprint(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+            bytes.iter().fold(hash, |hash, &b| (hash ^ b as u64).wrapping_mul(FNV_PRIME))
+        }
+
+        let mut hash = fnv1a(FNV_OFFSET_BASIS, &(self.len() as u64).to_le_bytes());
+        hash = self.query_index.iter().fold(hash, |h, q| fnv1a(h, &q.to_le_bytes()));
+        hash = fnv1a(hash, &self.distance);
+        hash = self.attribute.iter().fold(hash, |h, a| fnv1a(h, &a.to_le_bytes()));
+        hash = self.word_index.iter().fold(hash, |h, w| fnv1a(h, &w.to_le_bytes()));
+        self.is_exact.iter().fold(hash, |h, &e| fnv1a(h, &[e as u8]))
+    }
+
+    /// Returns the number of bytes allocated by the columns.
+    pub fn memory_footprint(&self) -> usize {
+        use std::mem::size_of;
+
+        self.query_index.capacity() * size_of::<u32>()
+            + self.distance.capacity() * size_of::<u8>()
+            + self.attribute.capacity() * size_of::<u16>()
+            + self.word_index.capacity() * size_of::<u16>()
+            + self.is_exact.capacity() * size_of::<bool>()
+    }
+
+    /// Returns the row that would be first after sorting, in a single pass.
+    pub fn min_row(&self) -> Option<ClassicRef<'_>> {
+        (0..self.len()).map(|i| self.row(i)).min()
+    }
+
+    /// Returns the row that would be last after sorting, in a single pass.
+    pub fn max_row(&self) -> Option<ClassicRef<'_>> {
+        (0..self.len()).map(|i| self.row(i)).max()
+    }
+
+    /// Returns the row that would be at index `k` after sorting, selected
+    /// over a buffer of indices, the columns are not moved.
+    pub fn kth_smallest_row(&self, k: usize) -> Option<ClassicRef<'_>> {
+        if k >= self.len() {
+            return None;
+        }
+
+        let mut indices: Vec<usize> = (0..self.len()).collect();
+        let (_, &mut index, _) = indices.select_nth_unstable_by_key(k, |&i| self.row(i));
+        Some(self.row(index))
+    }
+
+    /// Sorts the rows by the five fields, in declaration order,
+    /// like `sort_unstable` would on the equivalent `Vec<Classic>`.
+    pub fn sort_unstable(&mut self) {
+        // descending rows only need to be reversed, the scan
+        // stops at the first ascending pair on other inputs
+        if (1..self.len()).all(|i| self.row(i - 1) >= self.row(i)) {
+            return self.reverse();
+        }
+
+        let permutations = self.argsort_unstable();
+        self.apply_permutation(&permutations);
+    }
+
+    /// Sorts the rows like `sort_unstable` only if `validate` accepts all of
+    /// them, the first rejected row is reported and the rows are left untouched.
+    pub fn try_sort<F: Fn(ClassicRef<'_>) -> bool>(&mut self, validate: F) -> Result<(), Error> {
+        if let Some(row) = self.iter().position(|row| !validate(row)) {
+            return Err(Error::Validation { row });
+        }
+
+        self.sort_unstable();
+        Ok(())
+    }
+
+    /// Sorts the rows like `sort_unstable` and reports the time taken by the
+    /// argsort then by the scatter of each column, in that order, to `on_phase`.
+    pub fn sort_unstable_profiled<F: FnMut(Phase, Duration)>(&mut self, mut on_phase: F) {
+        let start = Instant::now();
+        let permutations = self.argsort_unstable();
+        on_phase(Phase::Argsort, start.elapsed());
+
+        for &column in &Column::ALL {
+            let start = Instant::now();
+            match column {
+                Column::QueryIndex => apply_permutations(&permutations, &mut self.query_index),
+                Column::Distance => apply_permutations(&permutations, &mut self.distance),
+                Column::Attribute => apply_permutations(&permutations, &mut self.attribute),
+                Column::WordIndex => apply_permutations(&permutations, &mut self.word_index),
+                Column::IsExact => apply_permutations(&permutations, &mut self.is_exact),
+            }
+            on_phase(Phase::Scatter(column), start.elapsed());
+        }
+    }
+
+    /// Sorts the rows like `sort_unstable` but the permutations buffer is reused as
+    /// the scratch of every column: the indices fit in the low 32 bits of the `usize`s,
+    /// the gathered values are written in the high ones then copied back. The
+    /// permutations are the only allocation instead of one more per column.
+    /// Falls back to `sort_unstable` when the indices don't leave room for a `u32`.
+    pub fn sort_unstable_scratch_reuse(&mut self) {
+        let mut permutations = self.argsort_unstable();
+        if mem::size_of::<usize>() < mem::size_of::<u64>() || self.len() > u32::MAX as usize {
+            return self.apply_permutation(&permutations);
+        }
+
+        gather_through_permutations(&mut permutations, &mut self.query_index, |q| q, |q| q);
+        gather_through_permutations(&mut permutations, &mut self.distance, u32::from, |d| d as u8);
+        gather_through_permutations(&mut permutations, &mut self.attribute, u32::from, |a| a as u16);
+        gather_through_permutations(&mut permutations, &mut self.word_index, u32::from, |w| w as u16);
+        gather_through_permutations(&mut permutations, &mut self.is_exact, u32::from, |e| e != 0);
+    }
+
+    /// Sorts the rows and reorders the external columns the same way,
+    /// every external column must have one value per row.
+    pub fn sort_unstable_and_permute(&mut self, externals: &mut [&mut dyn PermutableColumn]) {
+        assert!(
+            externals.iter().all(|column| column.len() == self.len()),
+            "the external columns must have one value per row",
+        );
+
+        let permutations = self.argsort_unstable();
+        self.apply_permutation(&permutations);
+        for column in externals {
+            column.apply_permutation(&permutations);
+        }
+    }
+
+    /// Returns the indices of the rows in sorted order, without moving them.
+    pub fn argsort_unstable(&self) -> Vec<usize> {
+        let mut permutations = Vec::new();
+        self.argsort_into(&mut permutations);
+        permutations
+    }
+
+    /// Replaces the content of `buf` by the indices of the rows in sorted order,
+    /// equal rows keep their relative order. It doesn't allocate when `buf`
+    /// already has the capacity for all the rows.
+    pub fn argsort_into(&self, buf: &mut Vec<usize>) {
+        argsort_columns(
+            &self.query_index,
+            &self.distance,
+            &self.attribute,
+            &self.word_index,
+            &self.is_exact,
+            buf,
+        );
+    }
+
+    /// Returns a sorted copy of the rows.
+    pub fn to_sorted(&self) -> DataOriented {
+        let mut sorted = DataOriented::default();
+        self.sort_into(&mut sorted);
+        sorted
+    }
+
+    /// Replaces the rows of `dst` by the rows of `self` in sorted order,
+    /// the columns allocations of `dst` are reused.
+    pub fn sort_into(&self, dst: &mut DataOriented) {
+        let permutations = self.argsort_unstable();
+
+        dst.clear();
+        dst.reserve(self.len());
+        dst.query_index.extend(permutations.iter().map(|&i| self.query_index[i]));
+        dst.distance.extend(permutations.iter().map(|&i| self.distance[i]));
+        dst.attribute.extend(permutations.iter().map(|&i| self.attribute[i]));
+        dst.word_index.extend(permutations.iter().map(|&i| self.word_index[i]));
+        dst.is_exact.extend(permutations.iter().map(|&i| self.is_exact[i]));
+    }
+
+    /// Returns, for each row, its position once sorted,
+    /// this is the inverse of the `argsort_unstable` permutation.
+    pub fn ranks(&self) -> Vec<usize> {
+        let mut ranks = vec![0; self.len()];
+        for (rank, i) in self.argsort_unstable().into_iter().enumerate() {
+            ranks[i] = rank;
+        }
+        ranks
+    }
+
+    /// Sorts the rows with a comparator over the row views. In debug builds the
+    /// comparator is checked to be a total order on a sample of rows first, an
+    /// inconsistent one panics instead of silently producing an unsorted result.
+    pub fn sort_unstable_by<F>(&mut self, mut compare: F)
+    where F: FnMut(ClassicRef<'_>, ClassicRef<'_>) -> Ordering,
+    {
+        #[cfg(debug_assertions)]
+        check_total_order(self.len(), |a, b| compare(self.row(a), self.row(b)));
+
+        let mut permutations: Vec<usize> = (0..self.len()).collect();
+        permutations.sort_unstable_by(|&a, &b| compare(self.row(a), self.row(b)));
+        self.apply_permutation(&permutations);
+    }
+
+    /// Sorts the rows by the key extracted from the row views.
+    pub fn sort_unstable_by_key<F, K>(&mut self, mut f: F)
+    where F: FnMut(ClassicRef<'_>) -> K,
+          K: Ord,
+    {
+        let permutations = permutations_unstable_by_key(self.len(), |i| f(self.row(i)));
+        self.apply_permutation(&permutations);
+    }
+
+    /// Sorts the rows by the given columns only, in the order of the rules,
+    /// each column being compared in its own direction.
+    pub fn sort_unstable_by_rules(&mut self, rules: &[(Column, SortOrder)]) {
+        let mut permutations: Vec<usize> = (0..self.len()).collect();
+        permutations.sort_unstable_by(|&a, &b| {
+            rules.iter().fold(Ordering::Equal, |ordering, &(column, order)| {
+                ordering.then_with(|| order.apply(self.compare_column(column, a, b)))
+            })
+        });
+
+        self.apply_permutation(&permutations);
+    }
+
+    /// Sorts the rows like `sort_unstable` but the rows with a `false` validity
+    /// have no `word_index`, they are ordered according to `nulls` and their stored
+    /// `word_index` is ignored. The validity is reordered along the rows.
+    pub fn sort_unstable_with_word_index_validity(&mut self, validity: &mut Vec<bool>, nulls: NullOrder) {
+        assert_eq!(self.len(), validity.len(), "the validity must have one value per row");
+
+        let permutations = permutations_unstable_by_key(self.len(), |i| {
+            let present = validity[i];
+            let word_index = (present == (nulls == NullOrder::First), if present { self.word_index[i] } else { 0 });
+            (self.query_index[i], self.distance[i], self.attribute[i], word_index, self.is_exact[i])
+        });
+
+        self.apply_permutation(&permutations);
+        apply_permutations(&permutations, validity);
+    }
+
+    /// Sorts the rows and reorders the `payload` column the same way,
+    /// the payload can hold any type that is expensive to clone.
+    pub fn sort_unstable_with_payload<P: PermutableColumn + ?Sized>(&mut self, payload: &mut P) {
+        assert_eq!(self.len(), payload.len(), "the payload must have one value per row");
+
+        let permutations = self.argsort_unstable();
+        self.apply_permutation(&permutations);
+        payload.apply_permutation(&permutations);
+    }
+
+    fn compare_column(&self, column: Column, a: usize, b: usize) -> Ordering {
+        match column {
+            Column::QueryIndex => self.query_index[a].cmp(&self.query_index[b]),
+            Column::Distance => self.distance[a].cmp(&self.distance[b]),
+            Column::Attribute => self.attribute[a].cmp(&self.attribute[b]),
+            Column::WordIndex => self.word_index[a].cmp(&self.word_index[b]),
+            Column::IsExact => self.is_exact[a].cmp(&self.is_exact[b]),
+        }
+    }
+
+    /// Sorts the rows like `sort_unstable` but first detects the columns holding
+    /// a single repeated value, they are not permuted as they are invariant under
+    /// any permutation. Returns these skipped columns. They are still compared by
+    /// the argsort, see the `*_constant_columns_16_000` benchmarks.
+    pub fn sort_unstable_skip_constant(&mut self) -> Vec<Column> {
+        fn is_constant<T: PartialEq>(column: &[T]) -> bool {
+            column.windows(2).all(|w| w[0] == w[1])
+        }
+
+        let (constant, varying): (Vec<_>, Vec<_>) = Column::ALL.iter().partition(|&&column| {
+            match column {
+                Column::QueryIndex => is_constant(&self.query_index),
+                Column::Distance => is_constant(&self.distance),
+                Column::Attribute => is_constant(&self.attribute),
+                Column::WordIndex => is_constant(&self.word_index),
+                Column::IsExact => is_constant(&self.is_exact),
+            }
+        });
+
+        let permutations = self.argsort_unstable();
+        for column in varying {
+            match column {
+                Column::QueryIndex => apply_permutations(&permutations, &mut self.query_index),
+                Column::Distance => apply_permutations(&permutations, &mut self.distance),
+                Column::Attribute => apply_permutations(&permutations, &mut self.attribute),
+                Column::WordIndex => apply_permutations(&permutations, &mut self.word_index),
+                Column::IsExact => apply_permutations(&permutations, &mut self.is_exact),
+            }
+        }
+
+        constant
+    }
+
+    /// Sorts the rows like `sort_unstable` with a least significant digit radix
+    /// sort, `attribute` and `word_index` are adjacent in priority and are
+    /// sorted as a single `u32` with two 16 bits digits instead of four bytes.
+    pub fn radix_sort(&mut self) {
+        let permutations = self.radix_argsort(true);
+        self.apply_permutation(&permutations);
+    }
+
+    /// The stable radix argsort, ties are therefore ordered by index like
+    /// `argsort_unstable`. With `group_u16` off the `attribute` and `word_index`
+    /// columns are sorted separately, bytewise like the other columns.
+    fn radix_argsort(&self, group_u16: bool) -> Vec<usize> {
+        fn pass<F: Fn(usize) -> usize>(src: &[usize], dst: &mut [usize], buckets: usize, digit: F) {
+            let mut offsets = vec![0; buckets + 1];
+            for &i in src {
+                offsets[digit(i) + 1] += 1;
+            }
+            for b in 1..offsets.len() {
+                offsets[b] += offsets[b - 1];
+            }
+            for &i in src {
+                let offset = &mut offsets[digit(i)];
+                dst[*offset] = i;
+                *offset += 1;
+            }
+        }
+
+        let mut src: Vec<usize> = (0..self.len()).collect();
+        let mut dst = vec![0; self.len()];
+        let mut sort_by = |buckets: usize, digit: &dyn Fn(usize) -> usize| {
+            pass(&src, &mut dst, buckets, digit);
+            std::mem::swap(&mut src, &mut dst);
+        };
+
+        // from the least significant column to the most significant one
+        sort_by(2, &|i| self.is_exact[i] as usize);
+        if group_u16 {
+            let group = |i: usize| (self.attribute[i] as usize) << 16 | self.word_index[i] as usize;
+            sort_by(1 << 16, &|i| group(i) & 0xFFFF);
+            sort_by(1 << 16, &|i| group(i) >> 16);
+        } else {
+            for shift in &[0, 8] {
+                sort_by(256, &|i| (self.word_index[i] >> shift) as usize & 0xFF);
+            }
+            for shift in &[0, 8] {
+                sort_by(256, &|i| (self.attribute[i] >> shift) as usize & 0xFF);
+            }
+        }
+        sort_by(256, &|i| self.distance[i] as usize);
+        for shift in &[0, 8, 16, 24] {
+            sort_by(256, &|i| (self.query_index[i] >> shift) as usize & 0xFF);
+        }
+
+        src
+    }
+
+    /// Reorders the rows by `distance` only with a stable counting sort and
+    /// returns the offsets of the buckets, the rows with the distance `d` are
+    /// in `offsets[d]..offsets[d + 1]`, in their original order.
+    pub fn bucket_sort_by_distance(&mut self) -> [usize; 257] {
+        let mut offsets = [0; 257];
+        for &distance in &self.distance {
+            offsets[distance as usize + 1] += 1;
+        }
+        for d in 1..offsets.len() {
+            offsets[d] += offsets[d - 1];
+        }
+
+        let mut permutations = vec![0; self.len()];
+        let mut next = offsets;
+        for (i, &distance) in self.distance.iter().enumerate() {
+            permutations[next[distance as usize]] = i;
+            next[distance as usize] += 1;
+        }
+
+        self.apply_permutation(&permutations);
+        offsets
+    }
+
+    /// Sorts the rows like `sort_unstable` but compares packed keys.
+    pub fn sort_unstable_packed(&mut self) {
+        let permutations = permutations_unstable_by_key(self.len(), |i| {
+            packed_key(
+                self.query_index[i],
+                self.distance[i],
+                self.attribute[i],
+                self.word_index[i],
+                self.is_exact[i],
+            )
+        });
+
+        self.apply_permutation(&permutations);
+    }
+
+    /// Sorts the rows like `sort_unstable` but computes the packed keys once into
+    /// a contiguous column, the comparisons never read the five columns. The row
+    /// index is stored in the 55 low bits left free by the 73 bits keys, making
+    /// the keys unique and ties ordered by index.
+    pub fn sort_via_key_column(&mut self) {
+        const INDEX_BITS: u32 = 128 - PACKED_KEY_BITS;
+        const INDEX_MASK: u128 = (1 << INDEX_BITS) - 1;
+        assert!((self.len() as u128) <= INDEX_MASK, "too many rows to be indexed in the keys");
+
+        let mut keys = self.packed_keys();
+        keys.iter_mut().enumerate().for_each(|(i, key)| *key = *key << INDEX_BITS | i as u128);
+        keys.sort_unstable();
+
+        let permutations: Vec<_> = keys.iter().map(|key| (key & INDEX_MASK) as usize).collect();
+        self.apply_permutation(&permutations);
+    }
+
+    /// Sorts the rows like `sort_unstable` by sorting pairs of packed key and
+    /// row index, the indices are moved with their keys, contiguously.
+    pub fn sort_via_key_index_pairs(&mut self) {
+        assert!(self.len() as u64 <= u32::MAX as u64, "too many rows to be indexed by u32");
+
+        let mut pairs: Vec<(u128, u32)> = self.packed_keys().into_iter().zip(0..).collect();
+        pairs.sort_unstable();
+
+        let permutations: Vec<_> = pairs.iter().map(|&(_, i)| i as usize).collect();
+        self.apply_permutation(&permutations);
+    }
+
+    /// Sorts the rows like `sort_unstable` but moves the rows in place, all the
+    /// columns at once, by following the permutation cycles a single time.
+    pub fn sort_in_place_fused(&mut self) {
+        let mut permutations = self.argsort_unstable();
+        permute_in_place_by(&mut permutations, |a, b| self.swap(a, b));
+    }
+
+    /// Sorts the rows like `sort_unstable` but only compares the first four fields,
+    /// `is_exact` is only used to break the ties of the equal runs, in a second pass.
+    pub fn sort_unstable_is_exact_tiebreak(&mut self) {
+        let key = |i: usize| (self.query_index[i], self.distance[i], self.attribute[i], self.word_index[i]);
+        let mut permutations = permutations_unstable_by_key(self.len(), key);
+
+        let mut start = 0;
+        for end in 1..=permutations.len() {
+            if end == permutations.len() || key(permutations[start]) != key(permutations[end]) {
+                if end - start > 1 {
+                    permutations[start..end].sort_unstable_by_key(|&i| self.is_exact[i]);
+                }
+                start = end;
+            }
+        }
+
+        self.apply_permutation(&permutations);
+    }
+
+    /// Sorts the rows for the rankings: the exact rows first then by `query_index`,
+    /// `distance`, `attribute` and `word_index`, in a single five fields sort.
+    pub fn sort_exact_first(&mut self) {
+        let permutations = permutations_unstable_by_key(self.len(), |i| {
+            (!self.is_exact[i], self.query_index[i], self.distance[i], self.attribute[i], self.word_index[i])
+        });
+
+        self.apply_permutation(&permutations);
+    }
+
+    /// Sorts the rows like `sort_exact_first` but first partitions the indices in
+    /// the exact and the inexact rows, in a single pass, then only compares the
+    /// four remaining fields inside each partition.
+    pub fn sort_partition_by_is_exact(&mut self) {
+        let exacts = self.is_exact.iter().filter(|&&e| e).count();
+        let mut permutations = vec![0; self.len()];
+        let (mut exact, mut inexact) = (0, exacts);
+        for (i, &is_exact) in self.is_exact.iter().enumerate() {
+            let cursor = if is_exact { &mut exact } else { &mut inexact };
+            permutations[*cursor] = i;
+            *cursor += 1;
+        }
+
+        let key = |&i: &usize| (self.query_index[i], self.distance[i], self.attribute[i], self.word_index[i]);
+        let (exact, inexact) = permutations.split_at_mut(exacts);
+        exact.sort_unstable_by_key(key);
+        inexact.sort_unstable_by_key(key);
+
+        self.apply_permutation(&permutations);
+    }
+
+    /// Sorts the rows like `sort_unstable` but first computes the packed keys
+    /// and compares them without branching, to reduce the mispredictions.
+    pub fn sort_unstable_branchless(&mut self) {
+        let keys = self.packed_keys();
+        let mut permutations: Vec<usize> = (0..self.len()).collect();
+        permutations.sort_unstable_by(|&a, &b| branchless_cmp(keys[a], keys[b]));
+
+        self.apply_permutation(&permutations);
+    }
+
+    /// Sorts the rows by packed keys with one direction per field, in the fields
+    /// order, e.g. the highest `query_index` first then the closest `distance`.
+    pub fn sort_packed_with_orders(&mut self, orders: [SortOrder; 5]) {
+        let permutations = permutations_unstable_by_key(self.len(), |i| {
+            packed_key_with_orders(
+                self.query_index[i],
+                self.distance[i],
+                self.attribute[i],
+                self.word_index[i],
+                self.is_exact[i],
+                orders,
+            )
+        });
+
+        self.apply_permutation(&permutations);
+    }
+
+    fn packed_keys(&self) -> Vec<u128> {
+        self.iter()
+            .map(|r| packed_key(*r.query_index, *r.distance, *r.attribute, *r.word_index, *r.is_exact))
+            .collect()
+    }
+
+    /// Sorts the rows like `sort_unstable` but first partitions them into
+    /// one bucket per distinct `query_index` value, in a counting sort fashion,
+    /// then only compares the four remaining fields inside each bucket.
+    ///
+    /// This is faster when `query_index` has a low cardinality, the buckets
+    /// are found in O(N log K) where K is the number of distinct values.
+    pub fn sort_unstable_two_level(&mut self) {
+        let mut counts = BTreeMap::new();
+        for &query_index in &self.query_index {
+            *counts.entry(query_index).or_insert(0) += 1;
+        }
+
+        // transform the counts into the write cursor of each bucket
+        let mut buckets = Vec::with_capacity(counts.len());
+        let mut start = 0;
+        for count in counts.values_mut() {
+            let end = start + *count;
+            buckets.push(start..end);
+            *count = start;
+            start = end;
+        }
+
+        let mut permutations = vec![0; self.len()];
+        for (i, query_index) in self.query_index.iter().enumerate() {
+            let cursor = counts.get_mut(query_index).unwrap();
+            permutations[*cursor] = i;
+            *cursor += 1;
+        }
+
+        for bucket in buckets {
+            permutations[bucket].sort_unstable_by_key(|&i| {
+                debug_assert!(i < self.len());
+                // SAFETY: the buckets are filled with the indices
+                // of the `query_index` column, all the columns have the same length.
+                unsafe {
+                    (
+                        self.distance.get_unchecked(i),
+                        self.attribute.get_unchecked(i),
+                        self.word_index.get_unchecked(i),
+                        self.is_exact.get_unchecked(i),
+                    )
+                }
+            });
+        }
+
+        self.apply_permutation(&permutations);
+    }
+
+    /// Sorts the rows and returns the contiguous range of rows
+    /// of each distinct `query_index` value, in ascending order.
+    pub fn sort_and_runs(&mut self) -> Vec<(u32, Range<usize>)> {
+        self.sort_unstable();
+
+        let mut runs = Vec::new();
+        let mut start = 0;
+        for (i, window) in self.query_index.windows(2).enumerate() {
+            if window[0] != window[1] {
+                runs.push((window[0], start..i + 1));
+                start = i + 1;
+            }
+        }
+
+        if let Some(&last) = self.query_index.last() {
+            runs.push((last, start..self.len()));
+        }
+
+        runs
+    }
+
+    /// Keeps only the first row of every run of equal `query_index`, the best
+    /// row of each query once the rows are sorted by `query_index` first.
+    pub fn keep_best_per_query_index(&mut self) {
+        debug_assert!(
+            self.query_index.windows(2).all(|w| w[0] <= w[1]),
+            "the rows must be sorted by query_index first",
+        );
+
+        let keep: Vec<_> = (0..self.len())
+            .map(|i| i == 0 || self.query_index[i - 1] != self.query_index[i])
+            .collect();
+        self.retain_rows(&keep);
+    }
+
+    fn retain_rows(&mut self, keep: &[bool]) {
+        fn retain<T>(column: &mut Vec<T>, keep: &[bool]) {
+            // `retain` visits the elements in order
+            let mut keep = keep.iter();
+            column.retain(|_| *keep.next().unwrap());
+        }
+
+        retain(&mut self.query_index, keep);
+        retain(&mut self.distance, keep);
+        retain(&mut self.attribute, keep);
+        retain(&mut self.word_index, keep);
+        retain(&mut self.is_exact, keep);
+    }
+
+    fn apply_permutation(&mut self, permutations: &[usize]) {
+        apply_permutations(permutations, &mut self.query_index);
+        apply_permutations(permutations, &mut self.distance);
+        apply_permutations(permutations, &mut self.attribute);
+        apply_permutations(permutations, &mut self.word_index);
+        apply_permutations(permutations, &mut self.is_exact);
+    }
+}
+
+pub fn permutations_unstable_by_key<F, K>(len: usize, mut f: F) -> Vec<usize>
+where F: FnMut(usize) -> K,
+      K: Ord,
+{
+    let mut permutations: Vec<usize> = (0..len).collect();
+    permutations.sort_unstable_by_key(|&i| f(i));
+    permutations
+}
+
+// this function is O(N) in term of memory but it could be O(1)
+// by following this blog post
+// https://devblogs.microsoft.com/oldnewthing/20170102-00/?p=95095
+pub fn apply_permutations<T: Clone>(permutations: &[usize], vec: &mut Vec<T>) {
+    assert_eq!(permutations.len(), vec.len());
+
+    // it is not necessary to restrict items to be Clone,
+    // we could ptr::read and, after having copied everything,
+    // set_len to 0 and drop the "empty" vec.
+    if cfg!(debug_assertions) {
+        check_permutation(permutations);
+    }
+
+    // collecting is faster than pushing in a loop, see the `gather_*` benchmarks
+    // the reads are bounds checked as the function is public and the
+    // permutations are only checked to be a bijection in debug builds
+    let new = permutations.iter().map(|&i| vec[i].clone()).collect();
+    *vec = new;
+}
+
+/// The default batch of `apply_permutations_batched`, the fastest of the
+/// `scatter_batched_*` benchmarks on 10M rows, the output of a batch of `u32`s
+/// is a single 4KiB page. Note that on the machine it was measured on, which
+/// uses transparent huge pages, the plain `apply_permutations` was still about
+/// three times faster, sorting the sources of the batches costs more than the
+/// TLB misses saved, check with the benchmarks before switching.
+pub const DEFAULT_SCATTER_BATCH: usize = 1_024;
+
+/// Applies the permutations like `apply_permutations` but one batch of `batch`
+/// output elements at a time, the sources of a batch are read in increasing
+/// order, which touches fewer pages at once than the random reads of a plain
+/// gather and reduces the TLB misses on very large columns.
+pub fn apply_permutations_batched<T: Clone>(permutations: &[usize], vec: &mut Vec<T>, batch: usize) {
+    assert_eq!(permutations.len(), vec.len());
+    assert!(batch > 0, "the batch can't be empty");
+
+    let mut new = Vec::with_capacity(permutations.len());
+    let output = &mut new.spare_capacity_mut()[..permutations.len()];
+    let mut sources = Vec::with_capacity(batch.min(permutations.len()));
+    for (permutations, output) in permutations.chunks(batch).zip(output.chunks_mut(batch)) {
+        sources.clear();
+        sources.extend(permutations.iter().enumerate().map(|(k, &i)| (i, k)));
+        sources.sort_unstable();
+
+        for &(i, k) in &sources {
+            output[k].write(vec[i].clone());
+        }
+    }
+
+    // SAFETY: every output element is written once, the `k`s of a batch
+    // are all the offsets of its output chunk.
+    unsafe { new.set_len(permutations.len()) };
+    *vec = new;
+}
+
+/// Applies the permutations by moving the elements instead of cloning them,
+/// the permutations are checked to be a bijection so that no element is moved twice.
+pub fn apply_permutations_moved<T>(permutations: &[usize], vec: &mut Vec<T>) {
+    assert_eq!(permutations.len(), vec.len());
+    assert!(is_permutation(permutations), "the permutations are not a bijection of 0..len");
+
+    let mut new = Vec::with_capacity(permutations.len());
+    // SAFETY: every element is read exactly once as the permutations are a bijection,
+    // the old vec forgets about them before being dropped, nothing can panic in between.
+    unsafe {
+        vec.set_len(0);
+        for &i in permutations {
+            new.push(ptr::read(vec.as_ptr().add(i)));
+        }
+    }
+    *vec = new;
+}
+
+/// Returns the permutations that undo `permutations`, `inverse[perm[k]] == k`.
+pub fn invert_permutation(permutations: &[usize]) -> Vec<usize> {
+    let mut inverse = vec![0; permutations.len()];
+    for (k, &i) in permutations.iter().enumerate() {
+        inverse[i] = k;
+    }
+    inverse
+}
+
+/// Checks that applying `permutations` then its inverse gives back `data`,
+/// to test the permutations produced by new sorts. Returns `false` for
+/// anything that is not a permutation of the rows.
+pub fn apply_and_invert_equals_identity(permutations: &[usize], data: &DataOriented) -> bool {
+    if permutations.len() != data.len() || !is_permutation(permutations) {
+        return false;
+    }
+
+    let mut permuted = data.clone();
+    permuted.apply_permutation(permutations);
+    permuted.apply_permutation(&invert_permutation(permutations));
+    permuted == *data
+}
+
+/// Panics if the permutations are not a bijection of `0..len`,
+/// naming the first index that is out of bounds or duplicated.
+fn check_permutation(permutations: &[usize]) {
+    let mut seen = vec![false; permutations.len()];
+    for &i in permutations {
+        assert!(i < seen.len(), "permutation index {} is out of bounds", i);
+        assert!(!std::mem::replace(&mut seen[i], true), "permutation index {} is duplicated", i);
+    }
+}
+
+fn is_permutation(permutations: &[usize]) -> bool {
+    let mut seen = vec![false; permutations.len()];
+    permutations.iter().all(|&i| i < seen.len() && !std::mem::replace(&mut seen[i], true))
+}
+
+/// A column that can be reordered along the rows of a `DataOriented`,
+/// cheap values are copied and the others are moved.
+pub trait PermutableColumn {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn apply_permutation(&mut self, permutations: &[usize]);
+}
+
+impl<C: PermutableColumn + ?Sized> PermutableColumn for &mut C {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn apply_permutation(&mut self, permutations: &[usize]) {
+        (**self).apply_permutation(permutations)
+    }
+}
+
+macro_rules! copy_permutable_column {
+    ($($t:ty),*) => {
+        $(
+            impl PermutableColumn for Vec<$t> {
+                fn len(&self) -> usize {
+                    Vec::len(self)
+                }
+
+                fn apply_permutation(&mut self, permutations: &[usize]) {
+                    apply_permutations(permutations, self)
+                }
+            }
+        )*
+    };
+}
+
+copy_permutable_column!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char);
+
+impl PermutableColumn for Vec<String> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn apply_permutation(&mut self, permutations: &[usize]) {
+        apply_permutations_moved(permutations, self)
+    }
+}
+
+impl<T> PermutableColumn for Vec<Vec<T>> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn apply_permutation(&mut self, permutations: &[usize]) {
+        apply_permutations_moved(permutations, self)
+    }
+}
+
+/// Applies the permutations in O(1) memory by following the permutation cycles,
+/// as described in the blog post above. The permutations are used to keep
+/// track of the already moved elements and are left as the identity.
+pub fn apply_permutations_in_place<T>(permutations: &mut [usize], slice: &mut [T]) {
+    assert_eq!(permutations.len(), slice.len());
+    permute_in_place_by(permutations, |a, b| slice.swap(a, b));
+}
+
+fn permute_in_place_by<F: FnMut(usize, usize)>(permutations: &mut [usize], mut swap: F) {
+    for i in 0..permutations.len() {
+        let mut current = i;
+        while permutations[current] != i {
+            let next = permutations[current];
+            // an already moved element in the middle of a cycle means that
+            // this is not a permutation, we would loop forever
+            assert_ne!(next, current, "the permutations contains duplicated indices");
+            swap(current, next);
+            permutations[current] = current;
+            current = next;
+        }
+        permutations[current] = current;
+    }
+}
+
+/// Gathers `column` in the high 32 bits of the `permutations`, whose indices are in
+/// the low 32 bits, then copies the gathered values back, see `sort_unstable_scratch_reuse`.
+fn gather_through_permutations<T: Copy>(
+    permutations: &mut [usize],
+    column: &mut [T],
+    to_bits: impl Fn(T) -> u32,
+    from_bits: impl Fn(u32) -> T,
+) {
+    const INDEX_MASK: u64 = u32::MAX as u64;
+    assert_eq!(permutations.len(), column.len());
+
+    for slot in permutations.iter_mut() {
+        let i = (*slot as u64 & INDEX_MASK) as usize;
+        *slot = (i as u64 | (to_bits(column[i]) as u64) << 32) as usize;
+    }
+
+    for (value, &slot) in column.iter_mut().zip(permutations.iter()) {
+        *value = from_bits((slot as u64 >> 32) as u32);
+    }
+}
+
+/// Applies the permutations like `apply_permutations` but into `scratch`,
+/// which is then swapped with `vec`, the allocations are reused between calls.
+pub fn apply_permutations_with_scratch<T: Clone>(
+    permutations: &[usize],
+    vec: &mut Vec<T>,
+    scratch: &mut Vec<T>,
+) {
+    assert_eq!(permutations.len(), vec.len());
+
+    scratch.clear();
+    scratch.extend(permutations.iter().map(|&i| vec[i].clone()));
+    std::mem::swap(vec, scratch);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn data_oriented_sort_is_valid() {
+        let length = 16_000;
+
+        let rng = StdRng::from_seed([42; 32]);
+        let mut classics = new_classics(rng, length);
+
+        let rng = StdRng::from_seed([42; 32]);
+        let mut data_oriented = DataOriented::new(rng, length);
+
+        // before sort
+        assert_eq!(data_oriented.validate_against(&classics), Ok(()));
+
+        // sort classics
+        classics.sort_unstable();
+
+        // sort data oriented
+        let permutations = permutations_unstable_by_key(data_oriented.len(), |i| {
+            debug_assert!(i < data_oriented.len());
+            // SAFETY: the indices are generated from `0..len`
+            // and all the columns have the same length.
+            unsafe {
+                (
+                    data_oriented.query_index.get_unchecked(i),
+                    data_oriented.distance.get_unchecked(i),
+                    data_oriented.attribute.get_unchecked(i),
+                    data_oriented.word_index.get_unchecked(i),
+                    data_oriented.is_exact.get_unchecked(i),
+                )
+            }
+        });
+
+        apply_permutations(&permutations, &mut data_oriented.query_index);
+        apply_permutations(&permutations, &mut data_oriented.distance);
+        apply_permutations(&permutations, &mut data_oriented.attribute);
+        apply_permutations(&permutations, &mut data_oriented.word_index);
+        apply_permutations(&permutations, &mut data_oriented.is_exact);
+
+        // after sort
+        assert_eq!(data_oriented.validate_against(&classics), Ok(()));
+    }
+
+    #[test]
+    fn validate_against_locates_mismatches() {
+        let data_oriented = DataOriented::from_seed(42, 100);
+        let mut classics: Vec<_> = data_oriented.iter().map(|r| r.to_classic()).collect();
+        assert_eq!(data_oriented.validate_against(&classics), Ok(()));
+
+        classics[42].word_index = classics[42].word_index.wrapping_add(1);
+        assert_eq!(data_oriented.validate_against(&classics), Err((42, Column::WordIndex)));
+
+        assert_eq!(data_oriented.validate_against(&classics[..10]), Err((10, Column::QueryIndex)));
+    }
+
+    #[test]
+    fn two_level_sort_is_valid() {
+        let length = 16_000;
+
+        let rng = StdRng::from_seed([42; 32]);
+        let mut data_oriented = DataOriented::new(rng, length);
+        data_oriented.query_index.iter_mut().for_each(|q| *q %= 8);
+
+        let mut two_level = data_oriented.clone();
+
+        data_oriented.sort_unstable();
+        two_level.sort_unstable_two_level();
+
+        assert_eq!(data_oriented, two_level);
+    }
+
+    #[test]
+    fn min_max_rows_are_sort_extremes() {
+        let rng = StdRng::from_seed([42; 32]);
+        let data_oriented = DataOriented::new(rng, 1_000);
+
+        let min = data_oriented.min_row().map(|r| r.to_classic());
+        let max = data_oriented.max_row().map(|r| r.to_classic());
+
+        let mut sorted = data_oriented.clone();
+        sorted.sort_unstable();
+
+        assert_eq!(min, sorted.get(0).map(|r| r.to_classic()));
+        assert_eq!(max, sorted.get(sorted.len() - 1).map(|r| r.to_classic()));
+
+        let empty = DataOriented::new(StdRng::from_seed([42; 32]), 0);
+        assert_eq!(empty.min_row(), None);
+        assert_eq!(empty.max_row(), None);
+    }
+
+    #[test]
+    fn packed_sort_is_valid() {
+        let mut data_oriented = DataOriented::from_seed(42, 16_000);
+        let mut packed = data_oriented.clone();
+
+        data_oriented.sort_unstable();
+        packed.sort_unstable_packed();
+
+        assert_eq!(data_oriented, packed);
+    }
+
+    #[test]
+    fn iter_mut_mutates_rows() {
+        let mut data_oriented = DataOriented::from_seed(42, 1_000);
+        let original = data_oriented.clone();
+
+        for row in data_oriented.iter_mut() {
+            *row.distance = 0;
+        }
+
+        assert!(data_oriented.iter().all(|row| *row.distance == 0));
+        assert_eq!(data_oriented.query_index, original.query_index);
+        assert_eq!(data_oriented.attribute, original.attribute);
+        assert_eq!(data_oriented.word_index, original.word_index);
+        assert_eq!(data_oriented.is_exact, original.is_exact);
+    }
+
+    #[test]
+    fn argsort_into_reuses_the_buffer() {
+        let data_oriented = DataOriented::from_seed(42, 1_000);
+        let expected = data_oriented.argsort_unstable();
+
+        let mut buf = Vec::new();
+        data_oriented.argsort_into(&mut buf);
+        assert_eq!(buf, expected);
+
+        let capacity = buf.capacity();
+        for _ in 0..3 {
+            data_oriented.argsort_into(&mut buf);
+            assert_eq!(buf, expected);
+            assert_eq!(buf.capacity(), capacity);
+        }
+    }
+
+    #[test]
+    fn ranks_invert_argsort() {
+        let data_oriented = DataOriented::from_seed(42, 1_000);
+
+        let argsort = data_oriented.argsort_unstable();
+        let ranks = data_oriented.ranks();
+
+        for (k, &i) in argsort.iter().enumerate() {
+            assert_eq!(ranks[i], k);
+        }
+    }
+
+    #[test]
+    fn sort_by_rules_matches_comparators() {
+        let mut data_oriented = DataOriented::from_seed(42, 2_000);
+        // reduce the cardinalities to have ties on the first rules
+        data_oriented.query_index.iter_mut().for_each(|q| *q %= 4);
+        data_oriented.distance.iter_mut().for_each(|d| *d %= 4);
+        data_oriented.attribute.iter_mut().for_each(|a| *a %= 4);
+
+        let classics: Vec<_> = data_oriented.iter().map(|r| r.to_classic()).collect();
+
+        use Column::*;
+        use SortOrder::*;
+
+        // the columns that are not part of the rules are not compared and can
+        // be in any order, only the projection of the rules columns is checked
+        let projection = |c: &Classic| (c.distance, c.query_index, c.is_exact);
+        let mut data = data_oriented.clone();
+        data.sort_unstable_by_rules(&[(Distance, Ascending), (QueryIndex, Descending), (IsExact, Ascending)]);
+        let mut expected = classics.clone();
+        expected.sort_by(|a, b| {
+            a.distance.cmp(&b.distance)
+                .then(b.query_index.cmp(&a.query_index))
+                .then(a.is_exact.cmp(&b.is_exact))
+        });
+        let found: Vec<_> = data.iter().map(|r| projection(&r.to_classic())).collect();
+        assert_eq!(found, expected.iter().map(projection).collect::<Vec<_>>());
+
+        let projection = |c: &Classic| (c.is_exact, c.attribute, c.word_index);
+        let mut data = data_oriented.clone();
+        data.sort_unstable_by_rules(&[(IsExact, Descending), (Attribute, Descending), (WordIndex, Ascending)]);
+        let mut expected = classics.clone();
+        expected.sort_by(|a, b| {
+            b.is_exact.cmp(&a.is_exact)
+                .then(b.attribute.cmp(&a.attribute))
+                .then(a.word_index.cmp(&b.word_index))
+        });
+        let found: Vec<_> = data.iter().map(|r| projection(&r.to_classic())).collect();
+        assert_eq!(found, expected.iter().map(projection).collect::<Vec<_>>());
+
+        let mut data = data_oriented.clone();
+        let rules: Vec<_> = Column::ALL.iter().map(|&c| (c, Ascending)).collect();
+        data.sort_unstable_by_rules(&rules);
+        let mut expected = classics;
+        expected.sort();
+        assert_eq!(data.iter().map(|r| r.to_classic()).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn from_iter_sorted_equals_collect_then_sort() {
+        let data_oriented = DataOriented::from_seed(42, 1_000).map_query_index(|q| q % 10);
+        let mut duplicated = data_oriented.clone().into_classics();
+        duplicated.extend(data_oriented.into_classics());
+
+        let sorted = DataOriented::from_iter_sorted(duplicated.clone());
+        assert!(sorted.is_sorted());
+        assert_eq!(sorted.len(), 2_000);
+
+        let mut expected = DataOriented::from_classics(duplicated);
+        expected.sort_unstable();
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn permutations_round_trip() {
+        use rand::seq::SliceRandom;
+
+        let data_oriented = DataOriented::from_seed(42, 1_000);
+        let mut permutations: Vec<usize> = (0..1_000).collect();
+        permutations.shuffle(&mut StdRng::seed_from_u64(42));
+
+        assert!(apply_and_invert_equals_identity(&permutations, &data_oriented));
+        assert!(apply_and_invert_equals_identity(&data_oriented.argsort_unstable(), &data_oriented));
+
+        let inverse = invert_permutation(&permutations);
+        assert!((0..1_000).all(|k| inverse[permutations[k]] == k));
+
+        permutations[0] = permutations[1];
+        assert!(!apply_and_invert_equals_identity(&permutations, &data_oriented));
+        assert!(!apply_and_invert_equals_identity(&permutations[1..], &data_oriented));
+    }
+
+    #[test]
+    fn distance_buckets_are_homogeneous() {
+        let original = DataOriented::from_seed(42, 1_000).map_distance(|d| d % 5 * 3);
+        let mut data_oriented = original.clone();
+        let offsets = data_oriented.bucket_sort_by_distance();
+
+        assert_eq!(offsets[0], 0);
+        assert_eq!(offsets[256], 1_000);
+        for d in 0..256 {
+            let bucket = offsets[d]..offsets[d + 1];
+            let expected: Vec<_> = original.iter().filter(|r| *r.distance as usize == d).collect();
+            let rows: Vec<_> = bucket.map(|i| data_oriented.row(i)).collect();
+            // the rows of the bucket are in their original order
+            assert_eq!(rows, expected);
+        }
+    }
+
+    #[test]
+    fn adjacent_pairs_locate_the_inversion() {
+        let mut data_oriented = DataOriented::from_seed(42, 100).to_sorted();
+        assert_eq!(data_oriented.adjacent_pairs().count(), 99);
+        assert!(data_oriented.adjacent_pairs().all(|(a, b)| compare_rows(a, b) != Ordering::Greater));
+
+        data_oriented.swap(40, 41);
+        let mut inversions = data_oriented.adjacent_pairs()
+            .enumerate()
+            .filter(|(_, (a, b))| compare_rows(*a, *b) == Ordering::Greater);
+
+        let (i, (a, b)) = inversions.next().unwrap();
+        assert_eq!(i, 40);
+        assert_eq!((Some(a), Some(b)), (data_oriented.get(40), data_oriented.get(41)));
+        assert!(inversions.next().is_none());
+
+        assert_eq!(DataOriented::from_seed(42, 1).adjacent_pairs().count(), 0);
+    }
+
+    #[test]
+    fn kth_smallest_row_is_the_sorted_row() {
+        let data_oriented = DataOriented::from_seed(42, 1_000).map_query_index(|q| q % 10);
+        let sorted = data_oriented.to_sorted();
+
+        for &k in &[0, 1, 250, 500, 999] {
+            assert_eq!(data_oriented.kth_smallest_row(k), sorted.get(k));
+        }
+        assert_eq!(data_oriented.kth_smallest_row(1_000), None);
+        assert_eq!(DataOriented::default().kth_smallest_row(0), None);
+    }
+
+    #[test]
+    fn externals_follow_the_rows() {
+        let mut data_oriented = DataOriented::from_seed(42, 100);
+        let mut ids: Vec<u64> = (0..100).collect();
+        let mut names: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+
+        let original = data_oriented.clone();
+        data_oriented.sort_unstable_and_permute(&mut [&mut ids, &mut names]);
+        assert_eq!(data_oriented, original.to_sorted());
+
+        for (k, row) in data_oriented.iter().enumerate() {
+            assert_eq!(original.get(ids[k] as usize), Some(row));
+            assert_eq!(names[k], ids[k].to_string());
+        }
+    }
+
+    #[test]
+    fn try_reserve_fails_without_aborting() {
+        let data_oriented = DataOriented::try_with_capacity(1_000).unwrap();
+        assert!(data_oriented.capacities().iter().all(|&c| c >= 1_000));
+
+        // the u32 column of this many rows can't be addressed
+        assert!(DataOriented::try_with_capacity(usize::MAX / 2).is_err());
+
+        let mut data_oriented = DataOriented::from_seed(42, 100);
+        let capacities = data_oriented.capacities();
+        assert!(data_oriented.try_reserve(usize::MAX - 10).is_err());
+        assert_eq!(data_oriented.capacities(), capacities);
+        assert_eq!(data_oriented, DataOriented::from_seed(42, 100));
+
+        // the first three columns grow before the `word_index` one fails
+        let error = Vec::<u16>::new().try_reserve(usize::MAX).unwrap_err();
+        let result = data_oriented.try_reserve_columns(|data, column| match column {
+            Column::QueryIndex => data.query_index.try_reserve(1_000),
+            Column::Distance => data.distance.try_reserve(1_000),
+            Column::Attribute => {
+                assert!(data.query_index.capacity() >= 1_100 && data.distance.capacity() >= 1_100);
+                data.attribute.try_reserve(1_000)
+            }
+            Column::WordIndex => Err(error.clone()),
+            Column::IsExact => unreachable!("the columns after the failing one are not reserved"),
+        });
+        assert_eq!(result, Err(error));
+        assert_eq!(data_oriented.capacities(), capacities);
+        assert_eq!(data_oriented, DataOriented::from_seed(42, 100));
+    }
+
+    #[test]
+    fn key_index_pairs_sort_is_valid() {
+        let data_oriented = DataOriented::from_seed(42, 10_000).map_distance(|d| d % 4);
+
+        let mut sorted = data_oriented.clone();
+        sorted.sort_via_key_index_pairs();
+        assert_eq!(sorted, data_oriented.to_sorted());
+    }
+
+    #[test]
+    fn key_column_sort_is_valid() {
+        let data_oriented = DataOriented::from_seed(42, 10_000).map_attribute(|a| a % 4);
+
+        let mut sorted = data_oriented.clone();
+        sorted.sort_via_key_column();
+        assert_eq!(sorted, data_oriented.to_sorted());
+
+        let mut empty = DataOriented::default();
+        empty.sort_via_key_column();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn compare_rows_follows_the_fields_precedence() {
+        let row = |q, d, a, w, e| DataOriented::from_classics(vec![Classic {
+            query_index: q,
+            distance: d,
+            attribute: a,
+            word_index: w,
+            is_exact: e,
+        }]);
+
+        let base = row(5, 5, 5, 5, false);
+        let base = base.row(0);
+        assert_eq!(compare_rows(base, base), Ordering::Equal);
+
+        // each field wins over all the following ones
+        let cases = [
+            (row(4, 9, 9, 9, true), row(6, 0, 0, 0, false)),
+            (row(5, 4, 9, 9, true), row(5, 6, 0, 0, false)),
+            (row(5, 5, 4, 9, true), row(5, 5, 6, 0, false)),
+            (row(5, 5, 5, 4, true), row(5, 5, 5, 6, false)),
+            (row(5, 5, 5, 5, false), row(5, 5, 5, 5, true)),
+        ];
+
+        for (less, greater) in &cases {
+            let (less, greater) = (less.row(0), greater.row(0));
+            assert_eq!(compare_rows(less, greater), Ordering::Less);
+            assert_eq!(compare_rows(greater, less), Ordering::Greater);
+            assert_eq!(compare_rows(less, greater), less.cmp(&greater));
+        }
+    }
+
+    #[test]
+    fn rotations_match_the_classics() {
+        let mut data_oriented = DataOriented::from_seed(42, 100);
+        let mut classics = data_oriented.clone().into_classics();
+
+        data_oriented.rotate_left(30);
+        classics.rotate_left(30);
+        assert_eq!(data_oriented, classics);
+
+        data_oriented.rotate_right(71);
+        classics.rotate_right(71);
+        assert_eq!(data_oriented, classics);
+
+        data_oriented.rotate_left(100);
+        assert_eq!(data_oriented, classics);
+    }
+
+    #[test]
+    fn radix_sort_groups_the_u16_columns() {
+        let data_oriented = DataOriented::from_seed(42, 10_000).map_query_index(|q| q % 100);
+
+        let grouped = data_oriented.radix_argsort(true);
+        assert_eq!(grouped, data_oriented.radix_argsort(false));
+        assert_eq!(grouped, data_oriented.argsort_unstable());
+
+        let mut sorted = data_oriented.clone();
+        sorted.radix_sort();
+        assert_eq!(sorted, data_oriented.to_sorted());
+    }
+
+    #[test]
+    fn invariants_detect_desynced_columns() {
+        let mut data_oriented = DataOriented::from_seed(42, 10);
+        assert!(data_oriented.check_invariants().is_ok());
+
+        data_oriented.word_index.pop();
+        let result = data_oriented.check_invariants();
+        assert!(matches!(result, Err(Error::LengthMismatch { column: Column::WordIndex, .. })));
+    }
+
+    #[test]
+    fn argsort_orders_ties_by_index() {
+        let data_oriented = DataOriented::from_seed(42, 10_000)
+            .map_query_index(|q| q % 2)
+            .map_distance(|d| d % 2)
+            .map_attribute(|a| a % 2)
+            .map_word_index(|w| w % 2);
+
+        let mut expected: Vec<usize> = (0..data_oriented.len()).collect();
+        expected.sort_by_key(|&i| data_oriented.row(i));
+
+        for _ in 0..3 {
+            assert_eq!(data_oriented.argsort_unstable(), expected);
+        }
+    }
+
+    #[test]
+    fn auto_layout_branches_agree() {
+        for &len in &[0, 1, AUTO_LAYOUT_CROSSOVER.saturating_sub(1), AUTO_LAYOUT_CROSSOVER, 1_000] {
+            let data_oriented = DataOriented::from_seed(len as u64, len);
+            let mut expected: Vec<_> = data_oriented.iter().map(|r| r.to_classic()).collect();
+            expected.sort_unstable();
+
+            let mut sorted = data_oriented.clone();
+            let report = sorted.sort_auto_layout();
+            assert_eq!(sorted, expected);
+            assert_eq!(data_oriented.into_classics().len(), len);
+
+            #[allow(clippy::absurd_extreme_comparisons)]
+            let layout = if len < AUTO_LAYOUT_CROSSOVER { Layout::Aos } else { Layout::Soa };
+            assert_eq!(report.layout, layout);
+        }
+    }
+
+    #[test]
+    fn fingerprint_is_stable_and_content_addressed() {
+        let data = DataOriented::from_seed(42, 1_000);
+        assert_eq!(data.fingerprint(), data.clone().fingerprint());
+
+        // pinned, a change of this value breaks the caches keyed by fingerprints
+        let rows = vec![
+            Classic { query_index: 1, distance: 2, attribute: 3, word_index: 4, is_exact: true },
+            Classic { query_index: 70_000, distance: 0, attribute: 300, word_index: 0, is_exact: false },
+        ];
+        assert_eq!(DataOriented::from_classics(rows).fingerprint(), 0x85e4_7128_8566_54df);
+
+        let mut changed = data.clone();
+        *changed.iter_mut().nth(500).unwrap().attribute ^= 1;
+        assert_ne!(changed.fingerprint(), data.fingerprint());
+
+        let mut changed = data.clone();
+        *changed.iter_mut().nth(999).unwrap().is_exact ^= true;
+        assert_ne!(changed.fingerprint(), data.fingerprint());
+
+        let mut swapped = data.clone();
+        swapped.swap(0, 1);
+        assert_ne!(swapped.fingerprint(), data.fingerprint());
+    }
+
+    #[test]
+    fn batched_scatter_matches_the_plain_one() {
+        use rand::seq::SliceRandom;
+
+        let mut permutations: Vec<usize> = (0..10_000).collect();
+        permutations.shuffle(&mut StdRng::seed_from_u64(42));
+        let column: Vec<u32> = (0..10_000).map(|i| i * 7).collect();
+
+        let mut expected = column.clone();
+        apply_permutations(&permutations, &mut expected);
+
+        for &batch in &[1, 3, 1_000, DEFAULT_SCATTER_BATCH, 20_000] {
+            let mut batched = column.clone();
+            apply_permutations_batched(&permutations, &mut batched, batch);
+            assert_eq!(batched, expected);
+        }
+    }
+
+    #[test]
+    fn packed_key_fields_placement() {
+        assert_eq!(PACKED_KEY_BITS, 73);
+        assert_eq!(packed_key(u32::MAX, u8::MAX, u16::MAX, u16::MAX, true), (1 << PACKED_KEY_BITS) - 1);
+        assert_eq!(packed_key(0, 0, 0, 0, true), 1);
+        assert_eq!(packed_key(0, 0, 0, 1, false), 1 << WORD_INDEX_SHIFT);
+        assert_eq!(packed_key(0, 0, 1, 0, false), 1 << ATTRIBUTE_SHIFT);
+        assert_eq!(packed_key(0, 1, 0, 0, false), 1 << DISTANCE_SHIFT);
+        assert_eq!(packed_key(1, 0, 0, 0, false), 1 << QUERY_INDEX_SHIFT);
+    }
+
+    #[test]
+    fn packed_key_is_exact_tiebreak() {
+        let key = |is_exact, order| packed_key_with_is_exact_order(7, 2, 300, 5, is_exact, order);
+
+        // `false < true` like `bool`
+        assert!(key(false, SortOrder::Ascending) < key(true, SortOrder::Ascending));
+        assert_eq!(key(true, SortOrder::Ascending) - key(false, SortOrder::Ascending), 1);
+
+        // the exact rows first
+        assert!(key(true, SortOrder::Descending) < key(false, SortOrder::Descending));
+
+        // `is_exact` never wins over the other fields
+        let next = packed_key_with_is_exact_order(7, 2, 300, 6, false, SortOrder::Descending);
+        assert!(key(false, SortOrder::Descending) < next);
+        assert!(packed_key(7, 2, 300, 5, true) < packed_key(7, 2, 300, 6, false));
+    }
+
+    #[test]
+    fn packed_orders_match_a_mixed_comparator() {
+        use SortOrder::{Ascending, Descending};
+
+        let all_orders = [
+            [Descending, Ascending, Ascending, Ascending, Ascending],
+            [Ascending, Descending, Ascending, Descending, Ascending],
+            [Descending, Descending, Descending, Descending, Descending],
+            [Ascending, Ascending, Descending, Ascending, Descending],
+        ];
+
+        for (seed, orders) in all_orders.iter().enumerate() {
+            let data = DataOriented::from_seed(seed as u64, 2_000);
+
+            let mut expected = data.clone().into_classics();
+            expected.sort_by(|a, b| {
+                orders[0].apply(a.query_index.cmp(&b.query_index))
+                    .then_with(|| orders[1].apply(a.distance.cmp(&b.distance)))
+                    .then_with(|| orders[2].apply(a.attribute.cmp(&b.attribute)))
+                    .then_with(|| orders[3].apply(a.word_index.cmp(&b.word_index)))
+                    .then_with(|| orders[4].apply(a.is_exact.cmp(&b.is_exact)))
+            });
+
+            let mut data = data;
+            data.sort_packed_with_orders(*orders);
+            assert_eq!(data, expected);
+        }
+    }
+
+    #[test]
+    fn natural_runs_cover_nearly_sorted_rows() {
+        assert!(DataOriented::default().natural_runs().is_empty());
+
+        let mut data = DataOriented::from_seed(42, 1_000);
+        data.sort_unstable();
+        assert_eq!(data.natural_runs(), vec![Range { start: 0, end: 1_000 }]);
+
+        // a few misplaced rows
+        for &(a, b) in &[(10, 900), (300, 301), (512, 40)] {
+            data.swap(a, b);
+        }
+
+        let runs = data.natural_runs();
+        assert!(runs.len() > 1 && runs.len() <= 7, "{} runs", runs.len());
+        assert_eq!(runs.first().unwrap().start, 0);
+        assert_eq!(runs.last().unwrap().end, data.len());
+        for (run, next) in runs.iter().zip(&runs[1..]) {
+            assert_eq!(run.end, next.start);
+            assert!(data.row(run.end - 1) > data.row(next.start));
+        }
+        for run in runs {
+            assert!(run.clone().skip(1).all(|i| data.row(i - 1) <= data.row(i)), "{:?}", run);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "the comparator is not")]
+    fn intransitive_comparator_panics() {
+        // rock, paper, scissors
+        let mut data = DataOriented::from_seed(42, 1_000);
+        data.sort_unstable_by(|a, b| match (a.query_index % 3, b.query_index % 3) {
+            (x, y) if x == y => Ordering::Equal,
+            (x, y) if (x + 1) % 3 == y => Ordering::Less,
+            _ => Ordering::Greater,
+        });
+    }
+
+    #[test]
+    fn extend_from_classics_slice_appends_the_rows() {
+        let classics = new_classics(StdRng::seed_from_u64(42), 1_000);
+
+        let mut data = DataOriented::from_seed(7, 10);
+        let mut expected = data.clone();
+        expected.extend(classics.iter().cloned());
+
+        data.extend_from_classics_slice(&classics);
+        assert_eq!(data, expected);
+        data.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn profiled_sort_reports_every_phase() {
+        let mut data = DataOriented::from_seed(42, 100_000);
+        let expected = data.to_sorted();
+
+        let mut phases = Vec::new();
+        data.sort_unstable_profiled(|phase, elapsed| phases.push((phase, elapsed)));
+        assert_eq!(data, expected);
+
+        let expected_phases: Vec<_> = std::iter::once(Phase::Argsort)
+            .chain(Column::ALL.iter().map(|&c| Phase::Scatter(c)))
+            .collect();
+        assert_eq!(phases.iter().map(|&(p, _)| p).collect::<Vec<_>>(), expected_phases);
+        assert!(phases.iter().all(|&(_, elapsed)| elapsed > Duration::default()), "{:?}", phases);
+    }
+
+    #[test]
+    fn radix_sort_classics_matches_sort_unstable() {
+        let mut classics = new_classics(StdRng::seed_from_u64(42), 10_000);
+        let mut expected = classics.clone();
+        expected.sort_unstable();
+
+        radix_sort_classics(&mut classics);
+        assert_eq!(classics, expected);
+    }
+
+    #[test]
+    fn row_frequencies_count_the_duplicates() {
+        let row = |query_index, is_exact| Classic { query_index, distance: 1, attribute: 2, word_index: 3, is_exact };
+        let data = DataOriented::from_classics(vec![
+            row(7, true),
+            row(2, false),
+            row(7, true),
+            row(7, false),
+            row(2, false),
+            row(7, true),
+        ]);
+
+        let expected = vec![(row(2, false), 2), (row(7, false), 1), (row(7, true), 3)];
+        assert_eq!(data.row_frequencies(), expected);
+        assert!(DataOriented::default().row_frequencies().is_empty());
+
+        let data = DataOriented::from_seed(42, 1_000);
+        let frequencies = data.row_frequencies();
+        assert_eq!(frequencies.iter().map(|&(_, count)| count).sum::<usize>(), data.len());
+    }
+
+    #[test]
+    fn sort_auto_counts_low_cardinality_primaries() {
+        let data = DataOriented::from_seed(42, 10_000);
+        let low_cardinality = data.map_query_index(|q| q % 100);
+
+        let mut sorted = low_cardinality.clone();
+        let report = sorted.sort_auto();
+        assert_eq!(report.strategy, SortStrategy::Counting);
+        assert_eq!(sorted, low_cardinality.to_sorted());
+
+        let mut sorted = data.clone();
+        let report = sorted.sort_auto();
+        assert_eq!(report.strategy, SortStrategy::Comparison);
+        assert_eq!(sorted, data.to_sorted());
+
+        let mut tiny = DataOriented::from_seed(42, 3);
+        assert_eq!(tiny.sort_auto().strategy, SortStrategy::Insertion);
+        assert!(tiny.is_sorted());
+    }
+
+    #[test]
+    fn row_views_compare_with_owned_rows() {
+        let data = DataOriented::from_classics(vec![
+            Classic { query_index: 1, distance: 2, attribute: 3, word_index: 4, is_exact: false },
+        ]);
+        let view = data.row(0);
+
+        let equal = view.to_classic();
+        assert!(view == equal);
+        assert!(equal == view);
+        assert_eq!(view.partial_cmp(&equal), Some(Ordering::Equal));
+
+        let greater = Classic { is_exact: true, ..equal.clone() };
+        assert_eq!(view.partial_cmp(&greater), Some(Ordering::Less));
+        assert!(view < greater);
+        assert!(greater > view);
+        assert!(view != greater);
+
+        let less = Classic { query_index: 0, word_index: 9, ..equal };
+        assert_eq!(view.partial_cmp(&less), Some(Ordering::Greater));
+        assert_eq!(less.partial_cmp(&view), Some(Ordering::Less));
+    }
+
+    #[test]
+    fn scratch_reuse_sorts_like_sort_unstable() {
+        for &len in &[0, 1, 10, 1_000] {
+            let data = DataOriented::from_seed(42, len);
+            let mut sorted = data.clone();
+            sorted.sort_unstable_scratch_reuse();
+            assert_eq!(sorted, data.to_sorted());
+        }
+
+        // the values use every bit of the high half of the permutations
+        let data = DataOriented::from_seed(42, 1_000).map_query_index(|q| q | 1 << 31);
+        let mut sorted = data.clone();
+        sorted.sort_unstable_scratch_reuse();
+        assert_eq!(sorted, data.to_sorted());
+    }
+
+    #[test]
+    fn partition_by_is_exact_sorts_like_the_ranking() {
+        use SortOrder::{Ascending, Descending};
+
+        let data = DataOriented::from_seed(42, 10_000).map_query_index(|q| q % 50);
+
+        let mut expected = data.clone();
+        expected.sort_exact_first();
+        let mut by_rules = data.clone();
+        by_rules.sort_unstable_by_rules(&[
+            (Column::IsExact, Descending),
+            (Column::QueryIndex, Ascending),
+            (Column::Distance, Ascending),
+            (Column::Attribute, Ascending),
+            (Column::WordIndex, Ascending),
+        ]);
+        assert_eq!(by_rules, expected);
+
+        let mut partitioned = data;
+        partitioned.sort_partition_by_is_exact();
+        assert_eq!(partitioned, expected);
+
+        let exacts = partitioned.is_exact().iter().take_while(|&&e| e).count();
+        assert!(partitioned.is_exact()[exacts..].iter().all(|&e| !e));
+    }
+
+    #[test]
+    fn drain_matches_the_vec_drain() {
+        let mut data = DataOriented::from_seed(42, 1_000);
+        let mut classics = data.clone().into_classics();
+
+        let drained: Vec<_> = data.drain(100..250).collect();
+        let expected: Vec<_> = classics.drain(100..250).collect();
+        assert_eq!(drained, expected);
+        assert_eq!(data, classics);
+        data.check_invariants().unwrap();
+
+        // the rows are removed even when not yielded
+        drop(data.drain(0..10));
+        classics.drain(0..10);
+        assert_eq!(data, classics);
+        assert_eq!(data.len(), 840);
+    }
+
+    #[test]
+    fn clone_into_reuses_the_columns() {
+        let data_oriented = DataOriented::from_seed(42, 1_000);
+
+        let mut dst = DataOriented::from_seed(7, 2_000);
+        let query_index = dst.query_index.as_ptr();
+        data_oriented.clone_into(&mut dst);
+        assert_eq!(dst, data_oriented);
+        assert_eq!(dst.query_index.as_ptr(), query_index);
+
+        let mut dst = DataOriented::default();
+        dst.clone_from(&data_oriented);
+        assert_eq!(dst, data_oriented);
+    }
+
+    #[test]
+    fn auto_layout_reports_its_choices() {
+        let report = DataOriented::from_seed(42, 2).sort_auto_layout();
+        assert_eq!((report.strategy, report.layout), (SortStrategy::Insertion, Layout::Soa));
+
+        let report = DataOriented::from_seed(42, 100_000).sort_auto_layout();
+        assert_eq!((report.strategy, report.layout), (SortStrategy::Comparison, Layout::Soa));
+        assert!(report.elapsed_hint.is_some());
+    }
+
+    #[test]
+    fn reverse_sorted_sorts_ascending() {
+        let data_oriented = DataOriented::reverse_sorted(42, 1_000);
+        assert!((1..1_000).all(|i| data_oriented.row(i - 1) >= data_oriented.row(i)));
+
+        let mut expected: Vec<_> = data_oriented.iter().map(|r| r.to_classic()).collect();
+        expected.sort_unstable();
+
+        let mut sorted = data_oriented.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, expected);
+
+        let mut sorted = data_oriented;
+        sorted.sort_unstable_packed();
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn compares_with_classics() {
+        let data_oriented = DataOriented::from_seed(42, 100);
+        let mut classics: Vec<_> = data_oriented.iter().map(|r| r.to_classic()).collect();
+
+        assert_eq!(data_oriented, &classics[..]);
+        assert_eq!(data_oriented, classics);
+
+        classics[50].word_index = classics[50].word_index.wrapping_add(1);
+        assert_ne!(data_oriented, classics);
+
+        classics.truncate(99);
+        assert_ne!(data_oriented, &classics[..]);
+        assert_ne!(data_oriented, Vec::new());
+    }
+
+    #[test]
+    fn equal_rows_hash_the_same() {
+        use std::collections::HashSet;
+
+        let a = DataOriented::from_seed(42, 100);
+        let mut b = DataOriented::with_capacity(1000);
+        b.extend(a.iter().map(|r| r.to_classic()));
+
+        let mut set = HashSet::new();
+        set.insert(a);
+        set.insert(b);
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn rows_eq_ignores_capacities() {
+        let generated = DataOriented::from_seed(42, 100);
+
+        let mut pushed = DataOriented::with_capacity(1_000);
+        generated.iter().for_each(|r| pushed.push(r.to_classic()));
+
+        assert_ne!(generated.query_index.capacity(), pushed.query_index.capacity());
+        assert!(generated.rows_eq(&pushed));
+        assert_eq!(generated.first_difference(&pushed), None);
+
+        pushed.distance[42] = pushed.distance[42].wrapping_add(1);
+        assert!(!generated.rows_eq(&pushed));
+        assert_eq!(generated.first_difference(&pushed), Some(42));
+
+        let prefix = DataOriented::from_seed(42, 60);
+        assert!(!generated.rows_eq(&prefix));
+        assert_eq!(generated.first_difference(&prefix), Some(60));
+        assert_eq!(prefix.first_difference(&generated), Some(60));
+    }
+
+    #[test]
+    fn branchless_sort_is_valid() {
+        assert_eq!(branchless_cmp(1, 2), Ordering::Less);
+        assert_eq!(branchless_cmp(2, 2), Ordering::Equal);
+        assert_eq!(branchless_cmp(3, 2), Ordering::Greater);
+
+        let mut data_oriented = DataOriented::from_seed(42, 16_000);
+        let mut branchless = data_oriented.clone();
+
+        data_oriented.sort_unstable();
+        branchless.sort_unstable_branchless();
+
+        assert_eq!(data_oriented, branchless);
+    }
+
+    #[test]
+    fn correlated_generation_follows_probabilities() {
+        let correlations = Correlations { max_distance: 3, exact_when_zero_distance: 0.8, exact_otherwise: 0.1 };
+        let data_oriented = DataOriented::new_correlated(42, 200_000, correlations);
+
+        let frequency = |zero_distance: bool| {
+            let rows: Vec<_> = data_oriented.iter().filter(|r| (*r.distance == 0) == zero_distance).collect();
+            rows.iter().filter(|r| *r.is_exact).count() as f64 / rows.len() as f64
+        };
+
+        assert!(data_oriented.distance.iter().all(|&d| d <= 3));
+        assert!((frequency(true) - 0.8).abs() < 0.01, "{}", frequency(true));
+        assert!((frequency(false) - 0.1).abs() < 0.01, "{}", frequency(false));
+    }
+
+    #[test]
+    fn merge_k_sorted_shards() {
+        let mut shards = Vec::new();
+        let mut concatenation = DataOriented::default();
+        for seed in 0..8 {
+            let mut shard = DataOriented::from_seed(seed, 100 + seed as usize * 37);
+            shard.query_index.iter_mut().for_each(|q| *q %= 16);
+            shard.sort_unstable();
+            concatenation.append(&mut shard.clone());
+            shards.push(shard);
+        }
+
+        concatenation.sort_unstable();
+        assert_eq!(merge_sorted(&shards[0], &shards[1]), {
+            let mut both = shards[0].clone();
+            both.append(&mut shards[1].clone());
+            both.sort_unstable();
+            both
+        });
+
+        let merged = merge_k_sorted(shards);
+        assert!(merged.is_sorted());
+        assert_eq!(merged, concatenation);
+    }
+
+    #[test]
+    fn sort_into_reuses_dst() {
+        let mut dst = DataOriented::from_seed(0, 2_000);
+        let capacity = dst.query_index.capacity();
+
+        for seed in 1..4 {
+            let data = DataOriented::from_seed(seed, 1_000);
+            data.sort_into(&mut dst);
+
+            let mut expected = data.clone();
+            expected.sort_unstable();
+            assert_eq!(dst, expected);
+            assert_eq!(data.to_sorted(), expected);
+            assert_eq!(dst.query_index.capacity(), capacity);
+        }
+    }
+
+    #[test]
+    fn map_distance_leaves_other_columns() {
+        let data_oriented = DataOriented::from_seed(42, 1_000);
+        let mapped = data_oriented.map_distance(|d| d / 2);
+
+        assert!(data_oriented.distance.iter().zip(&mapped.distance).all(|(d, m)| d / 2 == *m));
+        assert_eq!(data_oriented.query_index, mapped.query_index);
+        assert_eq!(data_oriented.attribute, mapped.attribute);
+        assert_eq!(data_oriented.word_index, mapped.word_index);
+        assert_eq!(data_oriented.is_exact, mapped.is_exact);
+    }
+
+    #[test]
+    fn is_exact_tiebreak_sort_is_valid() {
+        let mut data_oriented = DataOriented::from_seed(42, 4_000);
+        // only 16 distinct four-field keys, differing by their is_exact
+        data_oriented.query_index.iter_mut().for_each(|q| *q %= 2);
+        data_oriented.distance.iter_mut().for_each(|d| *d %= 2);
+        data_oriented.attribute.iter_mut().for_each(|a| *a %= 2);
+        data_oriented.word_index.iter_mut().for_each(|w| *w %= 2);
+
+        let mut tiebreak = data_oriented.clone();
+
+        data_oriented.sort_unstable();
+        tiebreak.sort_unstable_is_exact_tiebreak();
+
+        assert_eq!(data_oriented, tiebreak);
+    }
+
+    #[test]
+    #[should_panic(expected = "rows 3 and 4 are not sorted")]
+    fn assert_sorted_names_inverted_rows() {
+        let data = DataOriented::from_columns(
+            vec![0, 1, 2, 5, 3, 6],
+            vec![0; 6],
+            vec![0; 6],
+            vec![0; 6],
+            vec![false; 6],
+        ).unwrap();
+
+        assert_sorted(&data.to_sorted());
+        assert_sorted(&data);
+    }
+
+    #[test]
+    fn fused_in_place_sort_is_valid() {
+        let mut data_oriented = DataOriented::from_seed(42, 16_000);
+        let mut fused = data_oriented.clone();
+
+        data_oriented.sort_unstable();
+        fused.sort_in_place_fused();
+
+        assert_eq!(data_oriented, fused);
+    }
+
+    #[test]
+    fn seeded_generation_is_deterministic() {
+        assert_eq!(DataOriented::from_seed(42, 1_000), DataOriented::from_seed(42, 1_000));
+        assert_ne!(DataOriented::from_seed(42, 1_000), DataOriented::from_seed(43, 1_000));
+
+        let correlations = Correlations::default();
+        assert_eq!(
+            DataOriented::new_correlated(42, 1_000, correlations),
+            DataOriented::new_correlated(42, 1_000, correlations),
+        );
+    }
+
+    #[test]
+    fn inversions_count_pairs_out_of_order() {
+        let n = 500;
+        let reversed = DataOriented::from_columns(
+            (0..n as u32).rev().collect(),
+            vec![0; n],
+            vec![0; n],
+            vec![0; n],
+            vec![false; n],
+        ).unwrap();
+        assert_eq!(reversed.inversions_by_key(), n * (n - 1) / 2);
+        assert_eq!(reversed.to_sorted().inversions_by_key(), 0);
+
+        let mut data_oriented = DataOriented::from_seed(42, 300);
+        data_oriented.query_index.iter_mut().for_each(|q| *q %= 4);
+        let brute_force = (0..data_oriented.len())
+            .flat_map(|i| (i + 1..data_oriented.len()).map(move |j| (i, j)))
+            .filter(|&(i, j)| data_oriented.row(i) > data_oriented.row(j))
+            .count();
+        assert_eq!(data_oriented.inversions_by_key(), brute_force);
+    }
+
+    #[test]
+    fn nullable_word_index_sort() {
+        let mut data_oriented = DataOriented::from_seed(42, 2_000);
+        data_oriented.query_index.iter_mut().for_each(|q| *q %= 2);
+        data_oriented.distance.iter_mut().for_each(|d| *d %= 2);
+        data_oriented.attribute.iter_mut().for_each(|a| *a %= 2);
+        data_oriented.word_index.iter_mut().for_each(|w| *w %= 8);
+        let validity: Vec<_> = (0..data_oriented.len()).map(|i| i % 3 != 0).collect();
+
+        type Row = (u32, u8, u16, Option<u16>, bool);
+        let rows = |data: &DataOriented, validity: &[bool]| -> Vec<Row> {
+            data.iter().zip(validity).map(|(r, &valid)| {
+                (*r.query_index, *r.distance, *r.attribute, valid.then_some(*r.word_index), *r.is_exact)
+            })
+            .collect()
+        };
+
+        for &nulls in &[NullOrder::First, NullOrder::Last] {
+            let mut data = data_oriented.clone();
+            let mut valid = validity.clone();
+            data.sort_unstable_with_word_index_validity(&mut valid, nulls);
+
+            let mut expected = rows(&data_oriented, &validity);
+            expected.sort_by(|a, b| {
+                let word_index = match (a.3, b.3, nulls) {
+                    (None, Some(_), NullOrder::Last) => Ordering::Greater,
+                    (Some(_), None, NullOrder::Last) => Ordering::Less,
+                    _ => a.3.cmp(&b.3),
+                };
+                (a.0, a.1, a.2).cmp(&(b.0, b.1, b.2)).then(word_index).then(a.4.cmp(&b.4))
+            });
+
+            assert_eq!(rows(&data, &valid), expected);
+        }
+    }
+
+    #[test]
+    fn insertion_sort_is_valid() {
+        for len in 0..=INSERTION_SORT_THRESHOLD + 1 {
+            let mut data_oriented = DataOriented::from_seed(len as u64, len);
+            data_oriented.query_index.iter_mut().for_each(|q| *q %= 3);
+
+            let mut expected: Vec<_> = data_oriented.iter().map(|r| r.to_classic()).collect();
+            expected.sort_unstable();
+
+            data_oriented.sort_unstable();
+            assert_eq!(data_oriented.validate_against(&expected), Ok(()));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "nightly")]
+    fn tuned_insertion_threshold_is_small() {
+        let threshold = tune_insertion_threshold();
+        assert!((8..=64).contains(&threshold), "{}", threshold);
+    }
+
+    #[test]
+    fn constant_columns_are_skipped() {
+        let data_oriented = DataOriented::from_seed(42, 1_000).map_query_index(|_| 7);
+        let expected = data_oriented.to_sorted();
+
+        let mut sorted = data_oriented.clone();
+        assert_eq!(sorted.sort_unstable_skip_constant(), vec![Column::QueryIndex]);
+        assert_eq!(sorted, expected);
+
+        // the constant columns keep their buffers, the other ones are scattered in new ones
+        let mut sorted = data_oriented.map_is_exact(|_| false);
+        let query_index = (sorted.query_index.as_ptr(), sorted.query_index.capacity());
+        let is_exact = (sorted.is_exact.as_ptr(), sorted.is_exact.capacity());
+        let distance = sorted.distance.as_ptr();
+        assert_eq!(sorted.sort_unstable_skip_constant(), vec![Column::QueryIndex, Column::IsExact]);
+        assert!(sorted.is_sorted());
+        assert_eq!((sorted.query_index.as_ptr(), sorted.query_index.capacity()), query_index);
+        assert_eq!((sorted.is_exact.as_ptr(), sorted.is_exact.capacity()), is_exact);
+        assert_ne!(sorted.distance.as_ptr(), distance);
+
+        let mut sorted = DataOriented::from_seed(42, 1_000);
+        assert!(sorted.sort_unstable_skip_constant().is_empty());
+        assert!(sorted.is_sorted());
+    }
+
+    #[test]
+    fn keeps_the_best_row_per_query() {
+        let mut data_oriented = DataOriented::from_seed(42, 1_000).map_query_index(|q| q % 10);
+        data_oriented.sort_unstable();
+
+        let mut expected = DataOriented::default();
+        for (_, range) in data_oriented.clone().sort_and_runs() {
+            expected.push(data_oriented.get(range.start).unwrap().to_classic());
+        }
+
+        data_oriented.keep_best_per_query_index();
+        assert_eq!(data_oriented.len(), 10);
+        assert_eq!(data_oriented, expected);
+        assert_eq!(data_oriented.query_index(), &(0..10).collect::<Vec<_>>()[..]);
+    }
+
+    #[test]
+    fn sort_and_runs_cover_the_rows() {
+        let rng = StdRng::from_seed([42; 32]);
+        let mut data_oriented = DataOriented::new(rng, 1_000);
+        data_oriented.query_index.iter_mut().for_each(|q| *q %= 8);
+
+        let runs = data_oriented.sort_and_runs();
+        assert_eq!(runs.len(), 8);
+
+        let mut expected_start = 0;
+        for (query_index, range) in runs {
+            assert_eq!(range.start, expected_start);
+            assert!(data_oriented.query_index[range.clone()].iter().all(|&q| q == query_index));
+            expected_start = range.end;
+        }
+        assert_eq!(expected_start, data_oriented.len());
+
+        let mut empty = DataOriented::from_seed(42, 0);
+        assert!(empty.sort_and_runs().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn parallel_collect_preserves_order() {
+        let row = |i: u32| Classic {
+            query_index: i,
+            distance: (i % 7) as u8,
+            attribute: (i % 11) as u16,
+            word_index: (i % 13) as u16,
+            is_exact: i % 3 == 1,
+        };
+
+        let parallel: DataOriented = (0..100_000).into_par_iter().map(row).collect();
+        let sequential: DataOriented = (0..100_000).map(row).collect();
+
+        assert_eq!(parallel, sequential);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn parallel_iter_matches_iter() {
+        let data_oriented = DataOriented::from_seed(42, 100_000);
+        let score = |row: ClassicRef| *row.query_index as u64 * *row.distance as u64 + *row.attribute as u64;
+
+        let parallel: Vec<_> = data_oriented.par_iter().map(score).collect();
+        let sequential: Vec<_> = data_oriented.iter().map(score).collect();
+
+        assert_eq!(parallel, sequential);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn parallel_generation_is_deterministic() {
+        let length = 3 * PAR_CHUNK_LEN + 17;
+
+        let pool = |threads| rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        let one = pool(1).install(|| DataOriented::new_par(42, length));
+        let four = pool(4).install(|| DataOriented::new_par(42, length));
+
+        assert_eq!(one.len(), length);
+        assert_eq!(one, four);
+        assert_eq!(one, DataOriented::new_par(42, length));
+        assert_ne!(one, DataOriented::new_par(43, length));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "permutation index 1 is duplicated")]
+    fn duplicated_permutation_is_caught() {
+        let mut vec = vec![10, 20, 30];
+        apply_permutations(&[1, 2, 1], &mut vec);
+    }
+
+    #[test]
+    fn scatter_variants_are_equivalent() {
+        let data_oriented = DataOriented::from_seed(42, 1_000);
+        let permutations = data_oriented.argsort_unstable();
+
+        let mut allocating = data_oriented.query_index.clone();
+        apply_permutations(&permutations, &mut allocating);
+
+        let mut in_place = data_oriented.query_index.clone();
+        let mut consumed = permutations.clone();
+        apply_permutations_in_place(&mut consumed, &mut in_place);
+        assert!(consumed.iter().enumerate().all(|(i, &p)| i == p));
+
+        let mut with_scratch = data_oriented.query_index.clone();
+        let mut scratch = Vec::new();
+        apply_permutations_with_scratch(&permutations, &mut with_scratch, &mut scratch);
+
+        assert_eq!(allocating, in_place);
+        assert_eq!(allocating, with_scratch);
+    }
+
+    #[test]
+    fn sort_moves_string_payload() {
+        let mut data_oriented = DataOriented::from_seed(42, 1_000);
+        let mut payload: Vec<String> = data_oriented.iter().map(|r| format!("{:?}", r)).collect();
+
+        data_oriented.sort_unstable_with_payload(&mut payload);
+
+        let mut expected = data_oriented.clone();
+        expected.sort_unstable();
+        assert_eq!(data_oriented, expected);
+
+        for (row, value) in data_oriented.iter().zip(&payload) {
+            assert_eq!(&format!("{:?}", row), value);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not a bijection")]
+    fn moved_scatter_rejects_duplicates() {
+        let mut vec = vec![String::from("a"), String::from("b")];
+        apply_permutations_moved(&[1, 1], &mut vec);
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicated indices")]
+    fn in_place_scatter_rejects_duplicates() {
+        let mut vec = vec![10, 20, 30];
+        apply_permutations_in_place(&mut [1, 2, 1], &mut vec);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "permutation index 3 is out of bounds")]
+    fn out_of_range_permutation_is_caught() {
+        let mut vec = vec![10, 20, 30];
+        apply_permutations(&[0, 3, 1], &mut vec);
+    }
+}
+
+#[cfg(all(feature = "nightly", test))]
+mod bench {
+    extern crate test;
+
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+
+    fn shuffled_permutations(len: usize) -> Vec<usize> {
+        let mut rng = StdRng::from_seed([42; 32]);
+        let mut permutations: Vec<_> = (0..len).collect();
+        permutations.shuffle(&mut rng);
+        permutations
+    }
+
+    #[bench]
+    fn classics_16_000(b: &mut test::Bencher) {
+        let rng = StdRng::from_seed([42; 32]);
+        let data = new_classics(rng, 16_000);
+
+        b.iter(|| {
+            data.clone().sort_unstable();
+        })
+    }
+
+    #[bench]
     fn data_oriented_16_000(b: &mut test::Bencher) {
         let rng = StdRng::from_seed([42; 32]);
-        let data = DataOriented::new(rng, 16_000);
+        let data = DataOriented::new(rng, 16_000);
+
+        b.iter(|| {
+            let mut data = data.clone();
+            let permutations = permutations_unstable_by_key(data.len(), |i| {
+                debug_assert!(i < data.len());
+                // SAFETY: the indices are generated from `0..len`
+                // and all the columns have the same length.
+                unsafe {
+                    (
+                        data.query_index.get_unchecked(i),
+                        data.distance.get_unchecked(i),
+                        data.attribute.get_unchecked(i),
+                        data.word_index.get_unchecked(i),
+                        data.is_exact.get_unchecked(i),
+                    )
+                }
+            });
+
+            apply_permutations(&permutations, &mut data.query_index);
+            apply_permutations(&permutations, &mut data.distance);
+            apply_permutations(&permutations, &mut data.attribute);
+            apply_permutations(&permutations, &mut data.word_index);
+            apply_permutations(&permutations, &mut data.is_exact);
+        })
+    }
+
+    #[bench]
+    fn classics_packed_16_000(b: &mut test::Bencher) {
+        let rng = StdRng::from_seed([42; 32]);
+        let data = new_classics(rng, 16_000);
+
+        b.iter(|| {
+            data.clone().sort_unstable_by_key(|c| c.packed_key());
+        })
+    }
+
+    #[bench]
+    fn classics_packed_1_000_000(b: &mut test::Bencher) {
+        let rng = StdRng::from_seed([42; 32]);
+        let data = new_classics(rng, 1_000_000);
+
+        b.iter(|| {
+            data.clone().sort_unstable_by_key(|c| c.packed_key());
+        })
+    }
+
+    #[bench]
+    fn data_oriented_packed_16_000(b: &mut test::Bencher) {
+        let rng = StdRng::from_seed([42; 32]);
+        let data = DataOriented::new(rng, 16_000);
+
+        b.iter(|| {
+            data.clone().sort_unstable_packed();
+        })
+    }
+
+    #[bench]
+    fn data_oriented_packed_1_000_000(b: &mut test::Bencher) {
+        let rng = StdRng::from_seed([42; 32]);
+        let data = DataOriented::new(rng, 1_000_000);
+
+        b.iter(|| {
+            data.clone().sort_unstable_packed();
+        })
+    }
+
+    #[bench]
+    fn data_oriented_low_cardinality_16_000(b: &mut test::Bencher) {
+        let rng = StdRng::from_seed([42; 32]);
+        let mut data = DataOriented::new(rng, 16_000);
+        data.query_index.iter_mut().for_each(|q| *q %= 8);
+
+        b.iter(|| {
+            data.clone().sort_unstable();
+        })
+    }
+
+    #[bench]
+    fn data_oriented_two_level_low_cardinality_16_000(b: &mut test::Bencher) {
+        let rng = StdRng::from_seed([42; 32]);
+        let mut data = DataOriented::new(rng, 16_000);
+        data.query_index.iter_mut().for_each(|q| *q %= 8);
+
+        b.iter(|| {
+            data.clone().sort_unstable_two_level();
+        })
+    }
+
+    // On the machine they were written on skipping the scatter of the two constant
+    // columns is within the noise of the plain sort, the argsort dominates. Comparing
+    // the varying columns only, through `compare_column`, was about three times slower.
+
+    #[bench]
+    fn data_oriented_constant_columns_16_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 16_000).map_query_index(|_| 7).map_is_exact(|_| false);
+
+        b.iter(|| {
+            data.clone().sort_unstable();
+        })
+    }
+
+    #[bench]
+    fn skip_constant_columns_16_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 16_000).map_query_index(|_| 7).map_is_exact(|_| false);
+
+        b.iter(|| {
+            data.clone().sort_unstable_skip_constant();
+        })
+    }
+
+    // With 64 distinct `query_index` the counting sort of `sort_auto` wasn't faster
+    // than the plain sort on the machine they were last run on, it was slower on
+    // 16,000 rows and within the noise on 1,000,000 rows, which was very noisy.
+
+    #[bench]
+    fn data_oriented_low_cardinality_64_16_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 16_000).map_query_index(|q| q % 64);
+
+        b.iter(|| {
+            data.clone().sort_unstable();
+        })
+    }
+
+    #[bench]
+    fn sort_auto_low_cardinality_16_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 16_000).map_query_index(|q| q % 64);
+
+        b.iter(|| {
+            data.clone().sort_auto();
+        })
+    }
+
+    #[bench]
+    fn data_oriented_low_cardinality_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000).map_query_index(|q| q % 64);
+
+        b.iter(|| {
+            data.clone().sort_unstable();
+        })
+    }
+
+    #[bench]
+    fn sort_auto_low_cardinality_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000).map_query_index(|q| q % 64);
+
+        b.iter(|| {
+            data.clone().sort_auto();
+        })
+    }
+
+    // The three scatter benches reorder the same column with the same permutation
+    // at each iteration, the in-place one must also restore its consumed permutation,
+    // a memcpy of the permutation is therefore part of its measure.
+
+    #[bench]
+    fn scatter_allocating_1_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(1_000_000);
+        let mut column = DataOriented::from_seed(42, 1_000_000).query_index;
+
+        b.iter(|| {
+            apply_permutations(&permutations, &mut column);
+        })
+    }
+
+    #[bench]
+    fn scatter_in_place_1_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(1_000_000);
+        let mut consumed = permutations.clone();
+        let mut column = DataOriented::from_seed(42, 1_000_000).query_index;
+
+        b.iter(|| {
+            consumed.copy_from_slice(&permutations);
+            apply_permutations_in_place(&mut consumed, &mut column);
+        })
+    }
+
+    #[bench]
+    fn scatter_with_scratch_1_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(1_000_000);
+        let mut column = DataOriented::from_seed(42, 1_000_000).query_index;
+        let mut scratch = Vec::with_capacity(column.len());
+
+        b.iter(|| {
+            apply_permutations_with_scratch(&permutations, &mut column, &mut scratch);
+        })
+    }
+
+    // Only the indices are sorted, the keys are computed beforehand,
+    // to compare the comparators on random data, where branches mispredict.
+
+    #[bench]
+    fn argsort_packed_branchy_1_000_000(b: &mut test::Bencher) {
+        let keys = DataOriented::from_seed(42, 1_000_000).packed_keys();
+
+        b.iter(|| {
+            let mut permutations: Vec<usize> = (0..keys.len()).collect();
+            permutations.sort_unstable_by(|&a, &b| keys[a].cmp(&keys[b]));
+            permutations
+        })
+    }
+
+    #[bench]
+    fn argsort_packed_branchless_1_000_000(b: &mut test::Bencher) {
+        let keys = DataOriented::from_seed(42, 1_000_000).packed_keys();
+
+        b.iter(|| {
+            let mut permutations: Vec<usize> = (0..keys.len()).collect();
+            permutations.sort_unstable_by(|&a, &b| branchless_cmp(keys[a], keys[b]));
+            permutations
+        })
+    }
+
+    #[bench]
+    fn to_sorted_1_000_times_1_000(b: &mut test::Bencher) {
+        let instances: Vec<_> = (0..1_000).map(|seed| DataOriented::from_seed(seed, 1_000)).collect();
+
+        b.iter(|| {
+            for data in &instances {
+                test::black_box(data.to_sorted());
+            }
+        })
+    }
+
+    #[bench]
+    fn sort_into_1_000_times_1_000(b: &mut test::Bencher) {
+        let instances: Vec<_> = (0..1_000).map(|seed| DataOriented::from_seed(seed, 1_000)).collect();
+        let mut dst = DataOriented::with_capacity(1_000);
+
+        b.iter(|| {
+            for data in &instances {
+                data.sort_into(&mut dst);
+                test::black_box(&dst);
+            }
+        })
+    }
+
+    // The sort benches clone the data at each iteration, the following measure
+    // only the clone to be able to subtract it from the sort measures.
+
+    #[bench]
+    fn classics_clone_1_000_000(b: &mut test::Bencher) {
+        let rng = StdRng::from_seed([42; 32]);
+        let data = new_classics(rng, 1_000_000);
+
+        b.iter(|| data.clone())
+    }
+
+    #[bench]
+    fn data_oriented_clone_1_000_000(b: &mut test::Bencher) {
+        let rng = StdRng::from_seed([42; 32]);
+        let data = DataOriented::new(rng, 1_000_000);
+
+        b.iter(|| data.clone())
+    }
+
+    #[bench]
+    fn data_oriented_clone_into_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+        let mut dst = DataOriented::with_capacity(data.len());
+
+        b.iter(|| {
+            data.clone_into(&mut dst);
+            test::black_box(&dst);
+        })
+    }
+
+    #[bench]
+    fn data_oriented_five_passes_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| {
+            data.clone().sort_unstable();
+        })
+    }
+
+    #[bench]
+    fn data_oriented_in_place_fused_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| {
+            data.clone().sort_in_place_fused();
+        })
+    }
+
+    // Cold cache benchmarks.
+    //
+    // The benchmarks above run the sort over and over on the same data, which stays
+    // in the caches between iterations and can flatter the layouts. The following
+    // ones write over a scratch buffer larger than the last level cache between the
+    // clone and the sort, evicting the rows like for a first sort after construction.
+    //
+    // The flush itself is part of the measure, the `cold_flush_only_*` baselines run
+    // the same clone and flush without any sort and must be subtracted from the other
+    // numbers. On Linux the thread of each benchmark is pinned to the core it starts
+    // on, to avoid migrations between cores with different cache contents, the other
+    // platforms must be pinned from the outside, with `taskset -c 0` for example.
+
+    const CACHE_FLUSH_LEN: usize = 64 * 1024 * 1024;
+
+    #[cfg(target_os = "linux")]
+    fn pin_current_thread() {
+        extern "C" {
+            fn sched_getcpu() -> i32;
+            fn sched_setaffinity(pid: i32, cpusetsize: usize, mask: *const u64) -> i32;
+        }
+
+        // a `cpu_set_t` of the default 1024 cores
+        let mut mask = [0u64; 16];
+        // SAFETY: `sched_getcpu` has no precondition.
+        let cpu = unsafe { sched_getcpu() };
+        let pinned = cpu >= 0 && (cpu as usize) < mask.len() * 64 && {
+            mask[cpu as usize / 64] |= 1 << (cpu as usize % 64);
+            // SAFETY: the mask is a valid `cpu_set_t` of its size in bytes,
+            // the pid 0 designates the calling thread.
+            unsafe { sched_setaffinity(0, mem::size_of_val(&mask), mask.as_ptr()) == 0 }
+        };
+
+        if !pinned {
+            eprintln!("the thread could not be pinned: {}", std::io::Error::last_os_error());
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn pin_current_thread() {}
+
+    /// Pins the thread of the benchmark and allocates the buffer written by `flush_caches`.
+    fn cold_scratch() -> Vec<u8> {
+        pin_current_thread();
+        vec![0u8; CACHE_FLUSH_LEN]
+    }
+
+    fn flush_caches(scratch: &mut [u8]) {
+        for (i, byte) in scratch.iter_mut().enumerate().step_by(64) {
+            *byte = byte.wrapping_add(i as u8);
+        }
+        test::black_box(scratch);
+    }
+
+    #[bench]
+    fn cold_flush_only_classics_1_000_000(b: &mut test::Bencher) {
+        let rng = StdRng::from_seed([42; 32]);
+        let data = new_classics(rng, 1_000_000);
+        let mut scratch = cold_scratch();
+
+        b.iter(|| {
+            let data = data.clone();
+            flush_caches(&mut scratch);
+            data
+        })
+    }
+
+    #[bench]
+    fn cold_classics_1_000_000(b: &mut test::Bencher) {
+        let rng = StdRng::from_seed([42; 32]);
+        let data = new_classics(rng, 1_000_000);
+        let mut scratch = cold_scratch();
+
+        b.iter(|| {
+            let mut data = data.clone();
+            flush_caches(&mut scratch);
+            data.sort_unstable();
+            data
+        })
+    }
+
+    #[bench]
+    fn cold_flush_only_data_oriented_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+        let mut scratch = cold_scratch();
+
+        b.iter(|| {
+            let data = data.clone();
+            flush_caches(&mut scratch);
+            data
+        })
+    }
+
+    #[bench]
+    fn cold_data_oriented_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+        let mut scratch = cold_scratch();
+
+        b.iter(|| {
+            let mut data = data.clone();
+            flush_caches(&mut scratch);
+            data.sort_unstable();
+            data
+        })
+    }
+
+    #[bench]
+    fn classics_reverse_sorted_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::reverse_sorted(42, 1_000_000);
+        let data: Vec<_> = data.iter().map(|r| r.to_classic()).collect();
+
+        b.iter(|| {
+            data.clone().sort_unstable();
+        })
+    }
+
+    #[bench]
+    fn data_oriented_reverse_sorted_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::reverse_sorted(42, 1_000_000);
+
+        b.iter(|| {
+            data.clone().sort_unstable();
+        })
+    }
+
+    #[bench]
+    fn data_oriented_reverse_sorted_argsort_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::reverse_sorted(42, 1_000_000);
+
+        b.iter(|| {
+            let mut data = data.clone();
+            let permutations = data.argsort_unstable();
+            data.apply_permutation(&permutations);
+            data
+        })
+    }
+
+    // The gathers of `apply_permutations` written with `FromIterator`, which is
+    // what it uses, and with a `push` loop, compared on the two column widths.
+    // Both read with bounds checks, like `apply_permutations`. On the machine they
+    // were last run on collecting was about 30% faster on both widths, 2.7ms against
+    // 3.6ms on the `u32`s and 2.2ms against 3.2ms on the `u16`s.
+    fn apply_permutations_from_iter<T: Clone>(permutations: &[usize], vec: &mut Vec<T>) {
+        assert_eq!(permutations.len(), vec.len());
+        let new = permutations.iter().map(|&i| vec[i].clone()).collect();
+        *vec = new;
+    }
+
+    fn apply_permutations_push<T: Clone>(permutations: &[usize], vec: &mut Vec<T>) {
+        assert_eq!(permutations.len(), vec.len());
+        let mut new = Vec::with_capacity(permutations.len());
+        for &i in permutations {
+            new.push(vec[i].clone());
+        }
+        *vec = new;
+    }
+
+    #[bench]
+    fn gather_push_u32_1_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(1_000_000);
+        let mut column: Vec<u32> = (0..1_000_000).collect();
+
+        b.iter(|| apply_permutations_push(&permutations, &mut column))
+    }
+
+    #[bench]
+    fn gather_from_iter_u32_1_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(1_000_000);
+        let mut column: Vec<u32> = (0..1_000_000).collect();
+
+        b.iter(|| apply_permutations_from_iter(&permutations, &mut column))
+    }
+
+    #[bench]
+    fn gather_push_u16_1_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(1_000_000);
+        let mut column: Vec<u16> = (0..1_000_000).map(|i| i as u16).collect();
+
+        b.iter(|| apply_permutations_push(&permutations, &mut column))
+    }
+
+    #[bench]
+    fn gather_from_iter_u16_1_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(1_000_000);
+        let mut column: Vec<u16> = (0..1_000_000).map(|i| i as u16).collect();
+
+        b.iter(|| apply_permutations_from_iter(&permutations, &mut column))
+    }
+
+    // The same argsort with the row key helper forced to be inlined, left to the
+    // compiler, which is how `row` is called, or never inlined. On the machine they
+    // were last run on the forced and the default inlining took between 90ms and
+    // 126ms, within the noise of each other, and the never inlined helper between
+    // 178ms and 209ms, `row` is inlined by the compiler and has no hint. Neither
+    // have `len`, small enough to be inlined across the crates without a hint, and
+    // `apply_permutations`, which is generic and instantiated by its callers.
+    #[inline(always)]
+    fn row_key_always(data: &DataOriented, i: usize) -> ClassicRef<'_> {
+        data.row(i)
+    }
+
+    #[inline(never)]
+    fn row_key_never(data: &DataOriented, i: usize) -> ClassicRef<'_> {
+        data.row(i)
+    }
+
+    #[bench]
+    fn row_key_always_inlined_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| permutations_unstable_by_key(data.len(), |i| row_key_always(&data, i)))
+    }
+
+    #[bench]
+    fn row_key_default_inlining_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| permutations_unstable_by_key(data.len(), |i| data.row(i)))
+    }
+
+    #[bench]
+    fn row_key_never_inlined_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| permutations_unstable_by_key(data.len(), |i| row_key_never(&data, i)))
+    }
+
+    // The crossover of `sort_auto_layout`, the AoS round trip against the permutations.
+    // On the machine they were last run on the AoS round trip took between 259ns and
+    // 393ns from 1 to 4 rows, the permutations between 197ns and 303ns, each size was
+    // slower or within the noise through the AoS layout, like the larger sizes.
+
+    #[bench]
+    fn auto_layout_aos_1(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1);
+
+        b.iter(|| {
+            let mut classics = data.clone().into_classics();
+            classics.sort_unstable();
+            DataOriented::from_classics(classics)
+        })
+    }
+
+    #[bench]
+    fn auto_layout_soa_1(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1);
+
+        b.iter(|| {
+            let mut data = data.clone();
+            let permutations = data.argsort_unstable();
+            data.apply_permutation(&permutations);
+            data
+        })
+    }
+
+    #[bench]
+    fn auto_layout_aos_2(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 2);
+
+        b.iter(|| {
+            let mut classics = data.clone().into_classics();
+            classics.sort_unstable();
+            DataOriented::from_classics(classics)
+        })
+    }
+
+    #[bench]
+    fn auto_layout_soa_2(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 2);
+
+        b.iter(|| {
+            let mut data = data.clone();
+            let permutations = data.argsort_unstable();
+            data.apply_permutation(&permutations);
+            data
+        })
+    }
+
+    #[bench]
+    fn auto_layout_aos_4(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 4);
+
+        b.iter(|| {
+            let mut classics = data.clone().into_classics();
+            classics.sort_unstable();
+            DataOriented::from_classics(classics)
+        })
+    }
+
+    #[bench]
+    fn auto_layout_soa_4(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 4);
 
         b.iter(|| {
             let mut data = data.clone();
-            let permutations = permutations_unstable_by_key(data.len(), |i| unsafe {
-                (
-                    data.query_index.get_unchecked(i),
-                    data.distance.get_unchecked(i),
-                    data.attribute.get_unchecked(i),
-                    data.word_index.get_unchecked(i),
-                    data.is_exact.get_unchecked(i),
-                )
-            });
+            let permutations = data.argsort_unstable();
+            data.apply_permutation(&permutations);
+            data
+        })
+    }
 
-            apply_permutations(&permutations, &mut data.query_index);
-            apply_permutations(&permutations, &mut data.distance);
-            apply_permutations(&permutations, &mut data.attribute);
-            apply_permutations(&permutations, &mut data.word_index);
-            apply_permutations(&permutations, &mut data.is_exact);
+    #[bench]
+    fn auto_layout_aos_8(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 8);
+
+        b.iter(|| {
+            let mut classics = data.clone().into_classics();
+            classics.sort_unstable();
+            DataOriented::from_classics(classics)
+        })
+    }
+
+    #[bench]
+    fn auto_layout_soa_8(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 8);
+
+        b.iter(|| {
+            let mut data = data.clone();
+            let permutations = data.argsort_unstable();
+            data.apply_permutation(&permutations);
+            data
+        })
+    }
+
+    #[bench]
+    fn auto_layout_aos_128(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 128);
+
+        b.iter(|| {
+            let mut classics = data.clone().into_classics();
+            classics.sort_unstable();
+            DataOriented::from_classics(classics)
+        })
+    }
+
+    #[bench]
+    fn auto_layout_soa_128(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 128);
+
+        b.iter(|| {
+            let mut data = data.clone();
+            let permutations = data.argsort_unstable();
+            data.apply_permutation(&permutations);
+            data
+        })
+    }
+
+    #[bench]
+    fn auto_layout_aos_512(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 512);
+
+        b.iter(|| {
+            let mut classics = data.clone().into_classics();
+            classics.sort_unstable();
+            DataOriented::from_classics(classics)
+        })
+    }
+
+    #[bench]
+    fn auto_layout_soa_512(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 512);
+
+        b.iter(|| {
+            let mut data = data.clone();
+            let permutations = data.argsort_unstable();
+            data.apply_permutation(&permutations);
+            data
+        })
+    }
+
+    #[bench]
+    fn auto_layout_aos_2048(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 2048);
+
+        b.iter(|| {
+            let mut classics = data.clone().into_classics();
+            classics.sort_unstable();
+            DataOriented::from_classics(classics)
+        })
+    }
+
+    #[bench]
+    fn auto_layout_soa_2048(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 2048);
+
+        b.iter(|| {
+            let mut data = data.clone();
+            let permutations = data.argsort_unstable();
+            data.apply_permutation(&permutations);
+            data
+        })
+    }
+
+    #[bench]
+    fn auto_layout_aos_8192(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 8192);
+
+        b.iter(|| {
+            let mut classics = data.clone().into_classics();
+            classics.sort_unstable();
+            DataOriented::from_classics(classics)
+        })
+    }
+
+    #[bench]
+    fn auto_layout_soa_8192(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 8192);
+
+        b.iter(|| {
+            let mut data = data.clone();
+            let permutations = data.argsort_unstable();
+            data.apply_permutation(&permutations);
+            data
+        })
+    }
+
+    // The grouped digits halve the passes over the u16 columns
+    // but their 65536 counters don't fit in the L1 cache anymore.
+
+    #[bench]
+    fn radix_grouped_u16_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| data.radix_argsort(true))
+    }
+
+    #[bench]
+    fn radix_separate_u16_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| data.radix_argsort(false))
+    }
+
+    // The allocation and fill of the permutations vector alone, against the
+    // argsorts allocating it or reusing a buffer with `argsort_into`.
+    //
+    // On the machine they were written on the allocation is about 10% of a
+    // 16 rows argsort (18ns of 164ns) and reusing the buffer saves about 20ns,
+    // from 1_000 rows the allocation is below 1% of the argsort and is noise.
+    // Reusing the buffer is therefore only worth it for many tiny sorts.
+
+    #[bench]
+    fn permutations_alloc_16(b: &mut test::Bencher) {
+        b.iter(|| (0..test::black_box(16)).collect::<Vec<usize>>())
+    }
+
+    #[bench]
+    fn argsort_allocating_16(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 16);
+
+        b.iter(|| data.argsort_unstable())
+    }
+
+    #[bench]
+    fn argsort_reusing_16(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 16);
+        let mut buf = Vec::with_capacity(16);
+
+        b.iter(|| {
+            data.argsort_into(&mut buf);
+            test::black_box(&buf);
+        })
+    }
+
+    #[bench]
+    fn permutations_alloc_1_000(b: &mut test::Bencher) {
+        b.iter(|| (0..test::black_box(1_000)).collect::<Vec<usize>>())
+    }
+
+    #[bench]
+    fn argsort_allocating_1_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000);
+
+        b.iter(|| data.argsort_unstable())
+    }
+
+    #[bench]
+    fn argsort_reusing_1_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000);
+        let mut buf = Vec::with_capacity(1_000);
+
+        b.iter(|| {
+            data.argsort_into(&mut buf);
+            test::black_box(&buf);
+        })
+    }
+
+    #[bench]
+    fn permutations_alloc_100_000(b: &mut test::Bencher) {
+        b.iter(|| (0..test::black_box(100_000)).collect::<Vec<usize>>())
+    }
+
+    #[bench]
+    fn argsort_allocating_100_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 100_000);
+
+        b.iter(|| data.argsort_unstable())
+    }
+
+    #[bench]
+    fn argsort_reusing_100_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 100_000);
+        let mut buf = Vec::with_capacity(100_000);
+
+        b.iter(|| {
+            data.argsort_into(&mut buf);
+            test::black_box(&buf);
+        })
+    }
+
+    #[bench]
+    fn key_per_comparison_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| data.clone().sort_unstable_packed())
+    }
+
+    #[bench]
+    fn key_column_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| data.clone().sort_via_key_column())
+    }
+
+    #[bench]
+    fn key_index_pairs_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| data.clone().sort_via_key_index_pairs())
+    }
+
+    // The in-house radix argsort against the `radsort` crate, which sorts the
+    // packed keys themselves or the indices by their packed key, the latter is
+    // the same work as `radix_argsort` which reads the columns instead.
+
+    #[bench]
+    fn radsort_packed_keys_1_000_000(b: &mut test::Bencher) {
+        let keys = DataOriented::from_seed(42, 1_000_000).packed_keys();
+
+        b.iter(|| {
+            let mut keys = keys.clone();
+            radsort::sort(&mut keys);
+            keys
+        })
+    }
+
+    #[bench]
+    fn radsort_argsort_packed_keys_1_000_000(b: &mut test::Bencher) {
+        let keys = DataOriented::from_seed(42, 1_000_000).packed_keys();
+
+        b.iter(|| {
+            let mut permutations: Vec<usize> = (0..keys.len()).collect();
+            radsort::sort_by_key(&mut permutations, |&i| keys[i]);
+            permutations
+        })
+    }
+
+    #[bench]
+    fn radix_in_house_argsort_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| data.radix_argsort(true))
+    }
+
+    #[bench]
+    fn radsort_packed_keys_10_000_000(b: &mut test::Bencher) {
+        let keys = DataOriented::from_seed(42, 10_000_000).packed_keys();
+
+        b.iter(|| {
+            let mut keys = keys.clone();
+            radsort::sort(&mut keys);
+            keys
+        })
+    }
+
+    #[bench]
+    fn radsort_argsort_packed_keys_10_000_000(b: &mut test::Bencher) {
+        let keys = DataOriented::from_seed(42, 10_000_000).packed_keys();
+
+        b.iter(|| {
+            let mut permutations: Vec<usize> = (0..keys.len()).collect();
+            radsort::sort_by_key(&mut permutations, |&i| keys[i]);
+            permutations
+        })
+    }
+
+    #[bench]
+    fn radix_in_house_argsort_10_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 10_000_000);
+
+        b.iter(|| data.radix_argsort(true))
+    }
+
+    // Every column gathered into a new buffer, dropping the old one, against
+    // the old `attribute` buffer reused for `word_index`, saving one allocation
+    // and one deallocation of the five. Both are within the noise of each other,
+    // freeing a buffer costs nothing next to the gathers, the replace is kept.
+
+    fn apply_permutation_reusing(data: &mut DataOriented, permutations: &[usize]) {
+        apply_permutations(permutations, &mut data.query_index);
+        apply_permutations(permutations, &mut data.distance);
+        let mut scratch = Vec::new();
+        apply_permutations_with_scratch(permutations, &mut data.attribute, &mut scratch);
+        apply_permutations_with_scratch(permutations, &mut data.word_index, &mut scratch);
+        apply_permutations(permutations, &mut data.is_exact);
+    }
+
+    #[bench]
+    fn permutation_replace_10_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(10_000_000);
+        let mut data = DataOriented::from_seed(42, 10_000_000);
+
+        b.iter(|| data.apply_permutation(&permutations))
+    }
+
+    #[bench]
+    fn permutation_reuse_10_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(10_000_000);
+        let mut data = DataOriented::from_seed(42, 10_000_000);
+
+        b.iter(|| apply_permutation_reusing(&mut data, &permutations))
+    }
+
+    // The ranking order in a single five fields sort against the partition on
+    // `is_exact` then two four fields sorts, the partitioned one took about half
+    // the time here, with high variances.
+
+    #[bench]
+    fn exact_first_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| data.clone().sort_exact_first())
+    }
+
+    #[bench]
+    fn exact_first_partitioned_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| data.clone().sort_partition_by_is_exact())
+    }
+
+    // The same radix sort over both layouts, like `data_oriented_five_passes`
+    // the data is cloned at each iteration. Here the AoS one takes about two
+    // thirds of the time of the SoA one: a pass reads its digit next to the
+    // moved row while the SoA digits are read through the indices, at random.
+
+    #[bench]
+    fn radix_sort_classics_1_000_000(b: &mut test::Bencher) {
+        let classics = new_classics(StdRng::seed_from_u64(42), 1_000_000);
+
+        b.iter(|| {
+            let mut classics = classics.clone();
+            radix_sort_classics(&mut classics);
+            classics
+        })
+    }
+
+    #[bench]
+    fn radix_sort_data_oriented_1_000_000(b: &mut test::Bencher) {
+        let data = DataOriented::from_seed(42, 1_000_000);
+
+        b.iter(|| {
+            let mut data = data.clone();
+            data.radix_sort();
+            data
+        })
+    }
+
+    // Both are within the noise of each other, with fresh columns the
+    // time goes to the page faults and not to the copies.
+
+    #[bench]
+    fn extend_push_1_000_000(b: &mut test::Bencher) {
+        let classics = new_classics(StdRng::seed_from_u64(42), 1_000_000);
+
+        b.iter(|| {
+            let mut data = DataOriented::default();
+            data.extend(classics.iter().cloned());
+            data
+        })
+    }
+
+    #[bench]
+    fn extend_from_classics_slice_1_000_000(b: &mut test::Bencher) {
+        let classics = new_classics(StdRng::seed_from_u64(42), 1_000_000);
+
+        b.iter(|| {
+            let mut data = DataOriented::default();
+            data.extend_from_classics_slice(&classics);
+            data
         })
     }
+
+    // The plain gather against the batched one, on columns larger than what
+    // the TLB covers, `DEFAULT_SCATTER_BATCH` is the fastest of these sizes.
+
+    #[bench]
+    fn scatter_plain_10_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(10_000_000);
+        let mut column: Vec<u32> = (0..10_000_000).collect();
+
+        b.iter(|| apply_permutations(&permutations, &mut column))
+    }
+
+    #[bench]
+    fn scatter_batched_1_024_10_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(10_000_000);
+        let mut column: Vec<u32> = (0..10_000_000).collect();
+
+        b.iter(|| apply_permutations_batched(&permutations, &mut column, 1_024))
+    }
+
+    #[bench]
+    fn scatter_batched_4_096_10_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(10_000_000);
+        let mut column: Vec<u32> = (0..10_000_000).collect();
+
+        b.iter(|| apply_permutations_batched(&permutations, &mut column, 4_096))
+    }
+
+    #[bench]
+    fn scatter_batched_16_384_10_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(10_000_000);
+        let mut column: Vec<u32> = (0..10_000_000).collect();
+
+        b.iter(|| apply_permutations_batched(&permutations, &mut column, 16_384))
+    }
+
+    #[bench]
+    fn scatter_batched_65_536_10_000_000(b: &mut test::Bencher) {
+        let permutations = shuffled_permutations(10_000_000);
+        let mut column: Vec<u32> = (0..10_000_000).collect();
+
+        b.iter(|| apply_permutations_batched(&permutations, &mut column, 65_536))
+    }
+
+    // The cache misses per row of both sorts, read from the hardware counters of
+    // Linux and printed to stderr, the clone of each iteration is counted too, see
+    // `classics_clone_1_000_000`. Linux has no generic event for the L2 cache, the
+    // last level cache is counted instead. Without access to the counters, in a
+    // virtual machine or with a high `perf_event_paranoid`, only the time is measured.
+    //
+    //     cargo +nightly bench --features nightly cache_misses -- --nocapture
+    #[cfg(target_os = "linux")]
+    mod cache_misses {
+        use std::io;
+
+        use perf_event::events::{Cache, CacheOp, CacheResult, WhichCache};
+        use perf_event::{Builder, Counter, Group};
+
+        use super::*;
+
+        fn read_misses(which: WhichCache) -> Cache {
+            Cache { which, operation: CacheOp::READ, result: CacheResult::MISS }
+        }
+
+        fn counters() -> io::Result<(Group, Counter, Counter)> {
+            let mut group = Group::new()?;
+            let l1d = Builder::new().group(&mut group).kind(read_misses(WhichCache::L1D)).build()?;
+            let ll = Builder::new().group(&mut group).kind(read_misses(WhichCache::LL)).build()?;
+            Ok((group, l1d, ll))
+        }
+
+        fn bench_misses<F: FnMut()>(b: &mut test::Bencher, name: &str, rows: usize, mut sort: F) {
+            let (mut group, l1d, ll) = match counters() {
+                Ok(counters) => counters,
+                Err(error) => {
+                    eprintln!("{}: the cache misses can't be counted: {}", name, error);
+                    return b.iter(sort);
+                },
+            };
+
+            let mut iterations = 0;
+            b.iter(|| {
+                group.enable().unwrap();
+                sort();
+                group.disable().unwrap();
+                iterations += 1;
+            });
+
+            let counts = group.read().unwrap();
+            let per_row = |counter| counts[counter] as f64 / (iterations * rows) as f64;
+            eprintln!("{}: {:.3} L1D and {:.3} LL read misses per row", name, per_row(&l1d), per_row(&ll));
+        }
+
+        #[bench]
+        fn classics_cache_misses_1_000_000(b: &mut test::Bencher) {
+            let data = new_classics(StdRng::seed_from_u64(42), 1_000_000);
+
+            bench_misses(b, "classics", data.len(), || data.clone().sort_unstable());
+        }
+
+        #[bench]
+        fn data_oriented_cache_misses_1_000_000(b: &mut test::Bencher) {
+            let data = DataOriented::from_seed(42, 1_000_000);
+
+            bench_misses(b, "data_oriented", data.len(), || data.clone().sort_unstable());
+        }
+    }
 }
@@ -0,0 +1,215 @@
+//! The `write_columns` files viewed in place, for the datasets that don't fit in memory.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::slice;
+
+use memmap2::{Mmap, MmapMut};
+
+use crate::binary::{check_u32_indices, header, invalid_data, parse_header, write_u32_indices, HEADER_LEN};
+use crate::{argsort_columns, DataOriented, Error, PACKED_ROW_LEN};
+
+/// The columns of a file written by `DataOriented::write_columns`, memory-mapped
+/// and read without being copied. The file must not be modified while it is mapped.
+pub struct MmapDataOriented {
+    mmap: Mmap,
+    len: usize,
+}
+
+impl MmapDataOriented {
+    /// Maps the file and checks its header, its size and its `is_exact` bytes.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<MmapDataOriented, Error> {
+        let file = File::open(path)?;
+        // SAFETY: the file must not be modified while mapped, see the type documentation.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        let mut header = [0; HEADER_LEN];
+        let prefix = mmap.get(..HEADER_LEN).ok_or_else(|| invalid_data("the header is truncated"))?;
+        header.copy_from_slice(prefix);
+        let (len, flags) = parse_header(&header)?;
+        if flags != 0 {
+            return Err(invalid_data("compressed columns can't be viewed in place").into());
+        }
+
+        let expected = len.checked_mul(PACKED_ROW_LEN).and_then(|l| l.checked_add(HEADER_LEN));
+        if expected != Some(mmap.len()) {
+            return Err(invalid_data("the file size doesn't match the number of rows").into());
+        }
+
+        let data = MmapDataOriented { mmap, len };
+        if data.is_exact_bytes().iter().any(|&e| e > 1) {
+            return Err(invalid_data("is_exact must be 0 or 1").into());
+        }
+
+        Ok(data)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn column_ptr(&self, offset: usize) -> *const u8 {
+        self.mmap[HEADER_LEN + offset * self.len..].as_ptr()
+    }
+
+    pub fn query_index(&self) -> &[u32] {
+        // SAFETY: the map is page aligned and the header keeps the alignment, the
+        // size was checked when opened and the platform is little-endian.
+        unsafe { slice::from_raw_parts(self.column_ptr(0) as *const u32, self.len) }
+    }
+
+    pub fn attribute(&self) -> &[u16] {
+        // SAFETY: see `query_index`, the offset is a multiple of four.
+        unsafe { slice::from_raw_parts(self.column_ptr(4) as *const u16, self.len) }
+    }
+
+    pub fn word_index(&self) -> &[u16] {
+        // SAFETY: see `query_index`, the offset is a multiple of two.
+        unsafe { slice::from_raw_parts(self.column_ptr(6) as *const u16, self.len) }
+    }
+
+    pub fn distance(&self) -> &[u8] {
+        &self.mmap[HEADER_LEN + 8 * self.len..][..self.len]
+    }
+
+    fn is_exact_bytes(&self) -> &[u8] {
+        &self.mmap[HEADER_LEN + 9 * self.len..][..self.len]
+    }
+
+    pub fn is_exact(&self) -> &[bool] {
+        let bytes = self.is_exact_bytes();
+        // SAFETY: the bytes were checked to be zeros or ones when opened.
+        unsafe { slice::from_raw_parts(bytes.as_ptr() as *const bool, bytes.len()) }
+    }
+
+    /// Returns the indices of the rows in sorted order, like `DataOriented::argsort_unstable`.
+    pub fn argsort_unstable(&self) -> Vec<usize> {
+        let mut permutations = Vec::new();
+        argsort_columns(
+            self.query_index(),
+            self.distance(),
+            self.attribute(),
+            self.word_index(),
+            self.is_exact(),
+            &mut permutations,
+        );
+        permutations
+    }
+
+    /// Writes the sorted order of the rows like `DataOriented::write_sorted_indices`,
+    /// as little-endian `u32` indices, the rows are not copied. The writer should be buffered.
+    pub fn write_sorted_indices<W: Write>(&self, writer: W) -> io::Result<()> {
+        check_u32_indices(self.len)?;
+        write_u32_indices(&self.argsort_unstable(), writer)
+    }
+
+    /// Writes the sorted rows in a new memory-mapped file at `path`, in the
+    /// `write_columns` format, and maps it back. The rows are gathered column
+    /// by column so that a single column is read at a time. Fails when `path`
+    /// already exists, which protects the mapped source from being truncated.
+    pub fn write_sorted<P: AsRef<Path>>(&self, path: P) -> Result<MmapDataOriented, Error> {
+        let permutations = self.argsort_unstable();
+
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+        file.set_len((HEADER_LEN + PACKED_ROW_LEN * self.len) as u64)?;
+        // SAFETY: `create_new` made a new file, which is therefore neither the mapped
+        // source nor any other mapped file, it must not be modified while mapped.
+        let mut out = unsafe { MmapMut::map_mut(&file)? };
+
+        let (header_bytes, columns) = out.split_at_mut(HEADER_LEN);
+        header_bytes.copy_from_slice(&header(self.len, 0));
+        let (query_index, columns) = columns.split_at_mut(4 * self.len);
+        let (attribute, columns) = columns.split_at_mut(2 * self.len);
+        let (word_index, columns) = columns.split_at_mut(2 * self.len);
+        let (distance, is_exact) = columns.split_at_mut(self.len);
+
+        let q = self.query_index();
+        for (bytes, &i) in query_index.chunks_exact_mut(4).zip(&permutations) {
+            bytes.copy_from_slice(&q[i].to_le_bytes());
+        }
+        let a = self.attribute();
+        for (bytes, &i) in attribute.chunks_exact_mut(2).zip(&permutations) {
+            bytes.copy_from_slice(&a[i].to_le_bytes());
+        }
+        let w = self.word_index();
+        for (bytes, &i) in word_index.chunks_exact_mut(2).zip(&permutations) {
+            bytes.copy_from_slice(&w[i].to_le_bytes());
+        }
+        let d = self.distance();
+        distance.iter_mut().zip(&permutations).for_each(|(byte, &i)| *byte = d[i]);
+        let e = self.is_exact_bytes();
+        is_exact.iter_mut().zip(&permutations).for_each(|(byte, &i)| *byte = e[i]);
+
+        out.flush()?;
+        drop(out);
+
+        MmapDataOriented::open(path)
+    }
+
+    /// Copies the columns in memory.
+    pub fn to_data_oriented(&self) -> DataOriented {
+        DataOriented {
+            query_index: self.query_index().to_vec(),
+            distance: self.distance().to_vec(),
+            attribute: self.attribute().to_vec(),
+            word_index: self.word_index().to_vec(),
+            is_exact: self.is_exact().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+    use std::{fs, process};
+
+    /// A path that doesn't exist yet, under a random name in the temporary directory.
+    fn temp_path(name: &str) -> PathBuf {
+        let name = format!("data-oriented-sort-{}-{:016x}-{}", process::id(), rand::random::<u64>(), name);
+        std::env::temp_dir().join(name)
+    }
+
+    #[test]
+    fn argsorts_the_mapped_columns() {
+        let data = DataOriented::from_seed(42, 10_000);
+        let path = temp_path("unsorted");
+        let file = OpenOptions::new().write(true).create_new(true).open(&path).unwrap();
+        data.write_columns(io::BufWriter::new(file)).unwrap();
+
+        let mapped = MmapDataOriented::open(&path).unwrap();
+        assert_eq!(mapped.len(), data.len());
+        assert_eq!(mapped.to_data_oriented(), data);
+        assert_eq!(mapped.argsort_unstable(), data.argsort_unstable());
+
+        let mut indices = Vec::new();
+        mapped.write_sorted_indices(&mut indices).unwrap();
+        let indices: Vec<_> =
+            indices.chunks_exact(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize).collect();
+        assert_eq!(indices, data.argsort_unstable());
+
+        // the source is never truncated by its own sort
+        assert!(matches!(mapped.write_sorted(&path), Err(Error::Io(_))));
+        assert_eq!(mapped.to_data_oriented(), data);
+
+        let sorted_path = temp_path("sorted");
+        let sorted = mapped.write_sorted(&sorted_path).unwrap();
+        assert_eq!(sorted.to_data_oriented(), data.to_sorted());
+
+        // the files are not modified while they are mapped
+        let truncated = fs::read(&sorted_path).unwrap()[..20].to_vec();
+        drop(mapped);
+        drop(sorted);
+        fs::write(&path, truncated).unwrap();
+        assert!(matches!(MmapDataOriented::open(&path), Err(Error::Io(_))));
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(sorted_path).unwrap();
+    }
+}
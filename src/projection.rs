@@ -0,0 +1,94 @@
+use crate::{Column, DataOriented};
+
+/// A subset of the columns of a `DataOriented`, in the same row order,
+/// built by `DataOriented::select`. The other columns are `None`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Projection {
+    len: usize,
+    query_index: Option<Vec<u32>>,
+    distance: Option<Vec<u8>>,
+    attribute: Option<Vec<u16>>,
+    word_index: Option<Vec<u16>>,
+    is_exact: Option<Vec<bool>>,
+}
+
+impl DataOriented {
+    /// Copies the requested columns, a column requested twice is copied once.
+    pub fn select(&self, columns: &[Column]) -> Projection {
+        let mut projection = Projection { len: self.len(), ..Projection::default() };
+
+        for column in columns {
+            match column {
+                Column::QueryIndex => projection.query_index = Some(self.query_index.clone()),
+                Column::Distance => projection.distance = Some(self.distance.clone()),
+                Column::Attribute => projection.attribute = Some(self.attribute.clone()),
+                Column::WordIndex => projection.word_index = Some(self.word_index.clone()),
+                Column::IsExact => projection.is_exact = Some(self.is_exact.clone()),
+            }
+        }
+
+        projection
+    }
+}
+
+impl Projection {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the column was selected.
+    pub fn contains(&self, column: Column) -> bool {
+        match column {
+            Column::QueryIndex => self.query_index.is_some(),
+            Column::Distance => self.distance.is_some(),
+            Column::Attribute => self.attribute.is_some(),
+            Column::WordIndex => self.word_index.is_some(),
+            Column::IsExact => self.is_exact.is_some(),
+        }
+    }
+
+    pub fn query_index(&self) -> Option<&[u32]> {
+        self.query_index.as_deref()
+    }
+
+    pub fn distance(&self) -> Option<&[u8]> {
+        self.distance.as_deref()
+    }
+
+    pub fn attribute(&self) -> Option<&[u16]> {
+        self.attribute.as_deref()
+    }
+
+    pub fn word_index(&self) -> Option<&[u16]> {
+        self.word_index.as_deref()
+    }
+
+    pub fn is_exact(&self) -> Option<&[bool]> {
+        self.is_exact.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selected_columns_match_the_originals() {
+        let data = DataOriented::from_seed(42, 1_000).to_sorted();
+        let projection = data.select(&[Column::QueryIndex, Column::Distance]);
+
+        assert_eq!(projection.len(), data.len());
+        assert_eq!(projection.query_index(), Some(data.query_index()));
+        assert_eq!(projection.distance(), Some(data.distance()));
+        assert_eq!(projection.attribute(), None);
+        assert_eq!(projection.word_index(), None);
+        assert_eq!(projection.is_exact(), None);
+
+        let selected: Vec<_> = Column::ALL.iter().filter(|&&c| projection.contains(c)).collect();
+        assert_eq!(selected, [&Column::QueryIndex, &Column::Distance]);
+    }
+}
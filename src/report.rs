@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use crate::Column;
+
+/// The algorithm an adaptive sort ended up using.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortStrategy {
+    /// An insertion sort, for the inputs under `INSERTION_SORT_THRESHOLD` rows.
+    Insertion,
+    /// The comparison sort of the std, a pattern-defeating quicksort.
+    Comparison,
+    /// The least significant digit radix sort.
+    Radix,
+    /// A counting sort of `query_index` then a comparison sort of each bucket.
+    Counting,
+}
+
+/// The memory layout of the rows while they were sorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layout {
+    /// An array of structures, the rows were converted to a `Vec<Classic>`.
+    Aos,
+    /// A structure of arrays, the permutations were applied to the columns.
+    Soa,
+}
+
+/// Describes the choices of an adaptive sort, to log them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortReport {
+    pub strategy: SortStrategy,
+    pub layout: Layout,
+    /// The time the sort took, when it was measured.
+    pub elapsed_hint: Option<Duration>,
+}
+
+/// A step of `DataOriented::sort_unstable_profiled`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// The computation of the sorted order of the rows.
+    Argsort,
+    /// The permutation of one of the columns.
+    Scatter(Column),
+}
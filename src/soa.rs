@@ -0,0 +1,72 @@
+//! The `DataOriented` machinery for any set of columns: the rows are ordered
+//! by a key column and the same permutation is applied to every registered column.
+
+use crate::{apply_permutations, permutations_unstable_by_key, PermutableColumn};
+
+pub struct SoaSort<'a, K> {
+    keys: Vec<K>,
+    columns: Vec<Box<dyn PermutableColumn + 'a>>,
+}
+
+impl<'a, K: Ord + Copy> SoaSort<'a, K> {
+    /// Starts with one key per row, the number of keys defines the number of rows.
+    pub fn new(keys: Vec<K>) -> SoaSort<'a, K> {
+        SoaSort { keys, columns: Vec::new() }
+    }
+
+    /// Computes the key of each row from its index, this is done once, here,
+    /// so the function can borrow the columns that will be registered afterward.
+    pub fn by_key<F: FnMut(usize) -> K>(len: usize, f: F) -> SoaSort<'a, K> {
+        SoaSort::new((0..len).map(f).collect())
+    }
+
+    /// Registers a column to reorder, owned or a mutable reference,
+    /// it must have one value per key.
+    pub fn register<C: PermutableColumn + 'a>(&mut self, column: C) -> &mut SoaSort<'a, K> {
+        assert_eq!(column.len(), self.keys.len(), "the column must have one value per key");
+        self.columns.push(Box::new(column));
+        self
+    }
+
+    pub fn keys(&self) -> &[K] {
+        &self.keys
+    }
+
+    /// Sorts the keys and applies the same permutation to every registered column.
+    pub fn sort_unstable(&mut self) {
+        let keys = &self.keys;
+        let permutations = permutations_unstable_by_key(keys.len(), |i| keys[i]);
+
+        apply_permutations(&permutations, &mut self.keys);
+        for column in &mut self.columns {
+            column.apply_permutation(&permutations);
+        }
+    }
+
+    /// Releases the registered columns.
+    pub fn into_columns(self) -> Vec<Box<dyn PermutableColumn + 'a>> {
+        self.columns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_registered_columns() {
+        let mut ids: Vec<u64> = vec![5, 3, 9, 1, 7];
+        let mut names: Vec<String> = ["e", "cc", "i", "aaa", "g"].iter().map(|s| s.to_string()).collect();
+        let mut scores: Vec<f64> = vec![0.5, 0.3, 0.9, 0.1, 0.7];
+
+        // longest names first then by id
+        let mut soa = SoaSort::by_key(ids.len(), |i| (std::cmp::Reverse(names[i].len()), ids[i]));
+        soa.register(&mut ids).register(&mut names).register(&mut scores);
+        soa.sort_unstable();
+        drop(soa);
+
+        assert_eq!(ids, [1, 3, 5, 7, 9]);
+        assert_eq!(names, ["aaa", "cc", "e", "g", "i"]);
+        assert_eq!(scores, [0.1, 0.3, 0.5, 0.7, 0.9]);
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::BinaryHeap;
+
+use crate::{Classic, DataOriented};
+
+/// Keeps the `k` smallest, best, rows of a stream of rows, the memory
+/// used depends on `k` and not on the number of pushed rows.
+#[derive(Debug, Clone)]
+pub struct TopK {
+    k: usize,
+    // a max-heap, the worst of the kept rows is on top
+    heap: BinaryHeap<Classic>,
+}
+
+impl TopK {
+    pub fn new(k: usize) -> TopK {
+        TopK { k, heap: BinaryHeap::with_capacity(k) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Keeps the row if it is one of the `k` best rows seen so far,
+    /// evicting the current worst one when there already are `k` rows.
+    pub fn push(&mut self, row: Classic) {
+        if self.heap.len() < self.k {
+            self.heap.push(row);
+        } else if let Some(mut worst) = self.heap.peek_mut() {
+            if row < *worst {
+                *worst = row;
+            }
+        }
+    }
+
+    /// Returns the kept rows in sorted order.
+    pub fn into_sorted(self) -> DataOriented {
+        self.heap.into_sorted_vec().into_iter().collect()
+    }
+}
+
+impl Extend<Classic> for TopK {
+    fn extend<I: IntoIterator<Item = Classic>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|row| self.push(row));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streamed_top_k_is_the_sorted_prefix() {
+        let data = DataOriented::from_seed(42, 100_000);
+
+        let mut top = TopK::new(50);
+        top.extend(data.iter().map(|r| r.to_classic()));
+        assert_eq!(top.len(), 50);
+
+        let sorted = data.to_sorted();
+        let expected: Vec<_> = sorted.iter().take(50).map(|r| r.to_classic()).collect();
+        assert_eq!(top.into_sorted(), expected);
+    }
+
+    #[test]
+    fn fewer_rows_than_k() {
+        let data = DataOriented::from_seed(42, 10);
+
+        let mut top = TopK::new(50);
+        top.extend(data.iter().map(|r| r.to_classic()));
+        assert_eq!(top.into_sorted(), data.to_sorted());
+
+        let mut top = TopK::new(0);
+        top.extend(data.iter().map(|r| r.to_classic()));
+        assert!(top.is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+//! Small inputs going through every unsafe block of the crate, to be run under Miri
+//! which reports the undefined behaviors, the double drops and the leaks:
+//!
+//! ```text
+//! cargo +nightly miri test --lib --features "compact simd" unsafe_paths
+//! ```
+//!
+//! With `RUSTFLAGS="-C target-feature=+avx2"` the AVX2 gathers run under Miri too.
+//!
+//! They also run with the other tests, where they only check the results. The
+//! memory-mapped files can't be tested as Miri doesn't support `mmap`.
+
+use crate::*;
+
+fn permutations() -> Vec<usize> {
+    vec![3, 0, 4, 1, 5, 2, 6]
+}
+
+fn strings() -> Vec<String> {
+    (0..7).map(|i| format!("row number {}", i)).collect()
+}
+
+fn descending_rows() -> DataOriented {
+    (0..9)
+        .rev()
+        .map(|i| Classic { query_index: i, distance: 1, attribute: 2, word_index: 3, is_exact: i % 2 == 0 })
+        .collect()
+}
+
+#[test]
+fn gather_with_cloned_reads() {
+    let mut column: Vec<u32> = (10..17).collect();
+    apply_permutations(&permutations(), &mut column);
+    assert_eq!(column, [13, 10, 14, 11, 15, 12, 16]);
+
+    // a cloned `String` column, the old strings are dropped once
+    let mut column = strings();
+    apply_permutations(&permutations(), &mut column);
+    assert_eq!(column[0], "row number 3");
+}
+
+#[test]
+fn batched_gather_with_set_len() {
+    for batch in 1..=8 {
+        let mut column = strings();
+        apply_permutations_batched(&permutations(), &mut column, batch);
+        let expected: Vec<_> = permutations().iter().map(|i| format!("row number {}", i)).collect();
+        assert_eq!(column, expected);
+    }
+}
+
+#[test]
+fn moved_strings_are_read_once() {
+    let mut column = strings();
+    apply_permutations_moved(&permutations(), &mut column);
+    let expected: Vec<_> = permutations().iter().map(|i| format!("row number {}", i)).collect();
+    assert_eq!(column, expected);
+
+    let mut column: Vec<String> = Vec::new();
+    apply_permutations_moved(&[], &mut column);
+    assert!(column.is_empty());
+}
+
+#[test]
+fn in_place_cycles_swap_strings() {
+    let mut permutations = permutations();
+    let mut column = strings();
+    apply_permutations_in_place(&mut permutations, &mut column);
+    assert_eq!(permutations, [0, 1, 2, 3, 4, 5, 6]);
+    assert_eq!(column[2], "row number 4");
+}
+
+#[test]
+fn argsort_key_reads_unchecked() {
+    // both sides of the insertion sort threshold
+    for &len in &[5, INSERTION_SORT_THRESHOLD + 3] {
+        let data: DataOriented = (0..len as u32)
+            .map(|i| Classic {
+                query_index: i % 3,
+                distance: (i % 2) as u8,
+                attribute: 7,
+                word_index: (len as u32 - i) as u16,
+                is_exact: i % 4 == 0,
+            })
+            .collect();
+
+        let mut sorted = data.clone();
+        sorted.sort_unstable();
+        assert_sorted(&sorted);
+        sorted.sort_in_place_fused();
+        assert_sorted(&sorted);
+    }
+
+    let mut fused = descending_rows();
+    fused.sort_in_place_fused();
+    assert_eq!(fused, descending_rows().to_sorted());
+}
+
+#[test]
+fn branchless_comparison_transmutes() {
+    assert_eq!(branchless_cmp(1, 2), Ordering::Less);
+    assert_eq!(branchless_cmp(2, 2), Ordering::Equal);
+    assert_eq!(branchless_cmp(3, 2), Ordering::Greater);
+}
+
+#[cfg(feature = "compact")]
+#[test]
+fn compact_columns_sliced_from_one_buffer() {
+    let data = descending_rows();
+    let mut compact = CompactDataOriented::from(&data);
+    compact.sort_unstable();
+    assert_eq!(compact.to_data_oriented(), data.to_sorted());
+}
+
+#[cfg(feature = "simd")]
+#[test]
+fn aligned_buffers_allocated_and_freed() {
+    let data = descending_rows();
+    let mut aligned = AlignedDataOriented::from(&data);
+    aligned.sort_unstable();
+    assert_eq!(aligned.to_data_oriented(), data.to_sorted());
+
+    // 9 rows leave a scalar remainder, 17 rows a full chunk of 16 values
+    for &len in &[9, 17] {
+        let data = DataOriented::from_seed(42, len);
+        let mut aligned = AlignedDataOriented::from(&data);
+        aligned.sort_unstable_gather();
+        assert_eq!(aligned.to_data_oriented(), data.to_sorted());
+    }
+
+    let empty = DataOriented::aligned_zeroed(0);
+    assert!(empty.is_empty());
+}
@@ -0,0 +1,130 @@
+/// A primitive value that can be part of a sort key, its bits are
+/// transformed so that comparing them as unsigned integers gives the
+/// order of the values, which is what the packed keys and radix sorts need.
+pub trait ColumnValue: Copy {
+    /// The number of meaningful low bits returned by `to_sort_key_bits`.
+    const BITS: u32;
+
+    fn to_sort_key_bits(self) -> u64;
+
+    /// Appends the value to a packed key, after the already packed values
+    /// which therefore take precedence in the resulting order.
+    fn pack_into(self, key: u128) -> u128 {
+        key << Self::BITS | self.to_sort_key_bits() as u128
+    }
+}
+
+macro_rules! unsigned_column_value {
+    ($($t:ty),*) => {
+        $(
+            impl ColumnValue for $t {
+                const BITS: u32 = <$t>::BITS;
+
+                fn to_sort_key_bits(self) -> u64 {
+                    self as u64
+                }
+            }
+        )*
+    };
+}
+
+// the sign bit is flipped to order the negative values first
+macro_rules! signed_column_value {
+    ($($t:ty => $u:ty),*) => {
+        $(
+            impl ColumnValue for $t {
+                const BITS: u32 = <$t>::BITS;
+
+                fn to_sort_key_bits(self) -> u64 {
+                    (self as $u ^ 1 << (<$t>::BITS - 1)) as u64
+                }
+            }
+        )*
+    };
+}
+
+// the IEEE 754 total order: the negative values have all their bits flipped,
+// the positive ones only their sign bit, the negative NaNs are therefore ordered
+// before the negative infinity and the positive NaNs after the positive infinity
+macro_rules! float_column_value {
+    ($($t:ty => $u:ty),*) => {
+        $(
+            impl ColumnValue for $t {
+                const BITS: u32 = <$u>::BITS;
+
+                fn to_sort_key_bits(self) -> u64 {
+                    let bits = self.to_bits();
+                    let sign = 1 << (<$u>::BITS - 1);
+                    (if bits & sign != 0 { !bits } else { bits | sign }) as u64
+                }
+            }
+        )*
+    };
+}
+
+unsigned_column_value!(u8, u16, u32, u64);
+signed_column_value!(i8 => u8, i16 => u16, i32 => u32, i64 => u64);
+float_column_value!(f32 => u32, f64 => u64);
+
+impl ColumnValue for bool {
+    const BITS: u32 = 1;
+
+    fn to_sort_key_bits(self) -> u64 {
+        self as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::SoaSort;
+
+    #[test]
+    fn negatives_sort_before_positives() {
+        let values = [3, -1, i32::MIN, 0, i32::MAX, -42, 1];
+        let mut keys: Vec<_> = values.iter().map(|v| v.to_sort_key_bits()).collect();
+        keys.sort_unstable();
+
+        let mut expected = values;
+        expected.sort_unstable();
+        let expected: Vec<_> = expected.iter().map(|v| v.to_sort_key_bits()).collect();
+        assert_eq!(keys, expected);
+
+        let mut column = values.to_vec();
+        SoaSort::by_key(values.len(), |i| values[i].to_sort_key_bits())
+            .register(&mut column)
+            .sort_unstable();
+        assert_eq!(column, [i32::MIN, -42, -1, 0, 1, 3, i32::MAX]);
+    }
+
+    #[test]
+    fn floats_follow_the_total_order() {
+        let mut values = vec![1.5f32, -0.0, f32::NEG_INFINITY, 0.0, -2.5, f32::NAN, f32::INFINITY];
+        values.sort_unstable_by_key(|v| v.to_sort_key_bits());
+
+        let mut expected = values.clone();
+        expected.sort_unstable_by(f32::total_cmp);
+        let bits = |v: &[f32]| v.iter().map(|f| f.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&values), bits(&expected));
+    }
+
+    #[test]
+    fn nans_are_ordered_by_their_sign() {
+        let mut values = [f32::INFINITY, f32::NAN, 0.0, -f32::NAN, f32::NEG_INFINITY];
+        values.sort_unstable_by_key(|v| v.to_sort_key_bits());
+
+        assert!(values[0].is_nan() && values[0].is_sign_negative());
+        assert_eq!(values[1..4], [f32::NEG_INFINITY, 0.0, f32::INFINITY]);
+        assert!(values[4].is_nan() && values[4].is_sign_positive());
+    }
+
+    #[test]
+    fn packed_values_keep_their_precedence() {
+        let key = |a: i16, b: f64, c: bool| c.pack_into(b.pack_into(a.pack_into(0)));
+
+        assert!(key(-1, 9.0, true) < key(0, -9.0, false));
+        assert!(key(0, -9.0, true) < key(0, 1.0, false));
+        assert!(key(0, 1.0, false) < key(0, 1.0, true));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{argsort_columns, ClassicRef, DataOriented};
+
+/// A mutable window over the same rows of the five columns of a `DataOriented`,
+/// built by `DataOriented::chunks_mut`. The windows don't overlap and can be
+/// sorted independently, from different threads.
+#[derive(Debug)]
+pub struct DataOrientedViewMut<'a> {
+    query_index: &'a mut [u32],
+    distance: &'a mut [u8],
+    attribute: &'a mut [u16],
+    word_index: &'a mut [u16],
+    is_exact: &'a mut [bool],
+}
+
+fn apply_permutations_to_slice<T: Copy>(permutations: &[usize], slice: &mut [T]) {
+    let permuted: Vec<_> = permutations.iter().map(|&i| slice[i]).collect();
+    slice.copy_from_slice(&permuted);
+}
+
+impl DataOriented {
+    /// Splits the rows in windows of `size` rows, the last one can be shorter.
+    pub fn chunks_mut(&mut self, size: usize) -> impl Iterator<Item = DataOrientedViewMut<'_>> + '_ {
+        self.query_index
+            .chunks_mut(size)
+            .zip(self.distance.chunks_mut(size))
+            .zip(self.attribute.chunks_mut(size))
+            .zip(self.word_index.chunks_mut(size))
+            .zip(self.is_exact.chunks_mut(size))
+            .map(|((((query_index, distance), attribute), word_index), is_exact)| {
+                DataOrientedViewMut { query_index, distance, attribute, word_index, is_exact }
+            })
+    }
+}
+
+impl DataOrientedViewMut<'_> {
+    pub fn len(&self) -> usize {
+        self.query_index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.query_index.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<ClassicRef<'_>> {
+        Some(ClassicRef {
+            query_index: self.query_index.get(index)?,
+            distance: &self.distance[index],
+            attribute: &self.attribute[index],
+            word_index: &self.word_index[index],
+            is_exact: &self.is_exact[index],
+        })
+    }
+
+    /// Sorts the rows of the window like `DataOriented::sort_unstable`.
+    pub fn sort_unstable(&mut self) {
+        let mut permutations = Vec::new();
+        argsort_columns(
+            self.query_index,
+            self.distance,
+            self.attribute,
+            self.word_index,
+            self.is_exact,
+            &mut permutations,
+        );
+
+        apply_permutations_to_slice(&permutations, self.query_index);
+        apply_permutations_to_slice(&permutations, self.distance);
+        apply_permutations_to_slice(&permutations, self.attribute);
+        apply_permutations_to_slice(&permutations, self.word_index);
+        apply_permutations_to_slice(&permutations, self.is_exact);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_are_sorted_independently() {
+        let original = DataOriented::from_seed(42, 1_000);
+        let mut data = original.clone();
+
+        let mut lens = Vec::new();
+        for mut chunk in data.chunks_mut(300) {
+            chunk.sort_unstable();
+            lens.push(chunk.len());
+        }
+        assert_eq!(lens, [300, 300, 300, 100]);
+
+        for start in (0..1_000).step_by(300) {
+            let end = (start + 300).min(1_000);
+            let mut expected: Vec<_> = (start..end).map(|i| original.row(i).to_classic()).collect();
+            expected.sort_unstable();
+            let chunk: Vec<_> = (start..end).map(|i| data.row(i).to_classic()).collect();
+            assert_eq!(chunk, expected);
+        }
+    }
+}